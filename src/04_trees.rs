@@ -1,22 +1,37 @@
-struct TreeNode {
-    val: i32,
-    left: Option<Box<TreeNode>>,
-    right: Option<Box<TreeNode>>,
-}
+//! Binary trees with boxed children.
+//! `TreeNode` and its functions are in `tree_node.rs`, which is also used by `05_trees_HANDSON.rs`.
 
-fn tree_size(n: Option<Box<TreeNode>>) -> i32 {
-    if n.is_none() {
-        return 0;
-    }
+mod tree_node;
+use tree_node::*;
+
+
+
+pub fn main() {
+    println!("Hello, trees!");
 
-    let node = n.unwrap();
-    let size_left = tree_size(node.left);
-    let size_right = tree_size(node.right);
+    //      1
+    //     / \
+    //    2   3
+    //       /
+    //      4
+    let root = TreeNode {
+        val: 1,
+        left: Some(Box::new(TreeNode { val: 2, left: None, right: None })),
+        right: Some(Box::new(TreeNode {
+            val: 3,
+            left: Some(Box::new(TreeNode { val: 4, left: None, right: None })),
+            right: None,
+        })),
+    };
 
-    1 + size_left + size_right
+    println!("size: {}", tree_size(Some(&root)));
+    println!("height: {}", tree_height(Some(&root)));
+    println!("sum: {}", tree_sum(Some(&root)));
+    println!("leaves: {}", leaf_count(Some(&root)));
 }
 
 
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -24,7 +39,7 @@ mod tests {
     #[test]
     fn test_tree_size_empty() {
         let n: Option<Box<TreeNode>> = None;
-        let result = tree_size(n);
+        let result = tree_size(n.as_deref());
         assert_eq!(result, 0);
     }
 
@@ -35,7 +50,7 @@ mod tests {
             left: None,
             right: None,
         }));
-        let result = tree_size(n);
+        let result = tree_size(n.as_deref());
         assert_eq!(result, 1);
     }
 
@@ -58,14 +73,49 @@ mod tests {
                 right: None,
             })),
         }));
-        let result = tree_size(n);
+        let result = tree_size(n.as_deref());
         assert_eq!(result, 4);
+
+        // the tree is only borrowed
+        assert_eq!(tree_height(n.as_deref()), 3);
+        assert_eq!(tree_sum(n.as_deref()), 10);
+        assert_eq!(leaf_count(n.as_deref()), 2);
     }
 
-}
+    #[test]
+    fn test_empty_tree() {
+        assert_eq!(tree_height(None), 0);
+        assert_eq!(tree_sum(None), 0);
+        assert_eq!(leaf_count(None), 0);
+    }
 
+    #[test]
+    fn test_tree_sum_does_not_overflow() {
+        let n = TreeNode {
+            val: i32::MAX,
+            left: Some(Box::new(TreeNode { val: i32::MAX, left: None, right: None })),
+            right: None,
+        };
+        assert_eq!(tree_sum(Some(&n)), 2 * i32::MAX as i64);
+    }
 
+    #[test]
+    fn test_deep_chain() {
+        const CHAIN_LEN: i32 = 1_000_000;
 
-pub fn main() {
-    println!("Hello, trees!");
-}
\ No newline at end of file
+        // built from the bottom, so that every node is the left child of the next one
+        let mut n: Option<Box<TreeNode>> = None;
+        for val in 0..CHAIN_LEN {
+            n = Some(Box::new(TreeNode { val, left: n, right: None }));
+        }
+
+        assert_eq!(tree_size(n.as_deref()), CHAIN_LEN);
+        assert_eq!(tree_height(n.as_deref()), CHAIN_LEN);
+        assert_eq!(tree_sum(n.as_deref()), (CHAIN_LEN as i64 - 1) * CHAIN_LEN as i64 / 2);
+        assert_eq!(leaf_count(n.as_deref()), 1);
+
+        // dropping the chain does not overflow the stack
+        drop(n);
+    }
+
+}
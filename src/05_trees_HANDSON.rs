@@ -10,6 +10,7 @@
 extern crate core;
 use core::cmp::max;
 use core::cmp::min;
+use std::cmp::Ordering;
 
 
 /// set to true to print the tree using the `print_visualization_url()` method
@@ -22,14 +23,17 @@ const PRINT_TREE: bool = true;
 /// # Author
 /// - [Professor Venturini](https://pages.di.unipi.it/rossano/blog/2023/handson12324/)
 pub mod trees {
-    pub struct Node {
-        pub key: u32,
+    use std::fmt;
+    use std::ops::Add;
+
+    pub struct Node<K = u32> {
+        pub key: K,
         pub id_left: Option<usize>,
         pub id_right: Option<usize>,
     }
 
-    impl Node {
-        fn new(key: u32) -> Self {
+    impl<K> Node<K> {
+        fn new(key: K) -> Self {
             Self {
                 key,
                 id_left: None,
@@ -38,23 +42,76 @@ pub mod trees {
         }
     }
 
-    pub struct Tree {
-        pub nodes: Vec<Node>,
+    pub struct Tree<K = u32> {
+        pub nodes: Vec<Node<K>>,
+        /// ids of the slots of `nodes` that do not belong to the tree anymore, and will be reused by `add_node`
+        free_ids: Vec<usize>,
+        /// `is_free[id]` is `true` iff `id` is in `free_ids` (the slots after the end of `is_free` are not free)
+        is_free: Vec<bool>,
+    }
+
+    /// The error returned by the fallible operations on a `Tree`
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum TreeError {
+        /// `parent_id` is not the id of a node of the tree (out of range or a free slot)
+        MissingParent { parent_id: usize },
+        /// the left (iff `is_left` is `true`) or right child of `parent_id` is already the node `child_id`
+        OccupiedSlot { parent_id: usize, is_left: bool, child_id: usize },
+        /// `id` is not a node of the tree
+        MissingNode { id: usize },
+        /// the operation would remove the root, but a `Tree` always has a root
+        RootRemoval,
+        /// the operation needs a complete tree (e.g. a heap), see `is_complete()`
+        NotComplete,
+    }
+
+    impl fmt::Display for TreeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                TreeError::MissingParent { parent_id } => write!(f, "Parent node id {} does not exist", parent_id),
+                TreeError::OccupiedSlot { parent_id, is_left, child_id } => write!(
+                    f,
+                    "Parent node {} has the {} child already set (node {})",
+                    parent_id,
+                    if *is_left { "left" } else { "right" },
+                    child_id
+                ),
+                TreeError::MissingNode { id } => write!(f, "Node id {} is not in the tree", id),
+                TreeError::RootRemoval => write!(f, "The root cannot be removed"),
+                TreeError::NotComplete => write!(f, "The tree is not complete"),
+            }
+        }
     }
 
+    impl std::error::Error for TreeError {}
+
     /// This a representation of a tree.
     /// Every node has an implicity id, which is its position on the vector `nodes`.
     /// Every node has a key and at most two children. The ids of the children are
     /// stored in `id_left` and `id_right`. These ids are `None` iff the child does not exit.
-    impl Tree {
-        pub fn with_root(key: u32) -> Self {
+    /// The type of the keys is `K` (`u32` by default).
+    impl<K> Tree<K> {
+        pub fn with_root(key: K) -> Self {
             Self {
                 nodes: vec![Node::new(key)],
+                free_ids: Vec::new(),
+                is_free: vec![false],
             }
         }
 
-        pub fn get_node(&self, id: usize) -> Option<&Node> {
-            self.nodes.get(id)
+        /// # Returns
+        /// the node `id`, or `None` if `id` is out of range or it is a free slot
+        pub fn get_node(&self, id: usize) -> Option<&Node<K>> {
+            if self.is_live(id) { self.nodes.get(id) } else { None }
+        }
+
+        /// Builds a tree from its slots and its free list, without checking them (see `validate()`)
+        pub(crate) fn from_raw_parts(nodes: Vec<Node<K>>, free_ids: Vec<usize>) -> Self {
+            let mut is_free = vec![false; nodes.len()];
+            for &id in free_ids.iter().filter(|&&id| id < nodes.len()) {
+                is_free[id] = true;
+            }
+            Self { nodes, free_ids, is_free }
         }
 
         /// Adds a child to the node with `parent_id` and returns the id of the new node.
@@ -62,26 +119,35 @@ pub mod trees {
         /// iff `is_left` is `true`, the right child otherwise.
         ///
         /// # Panics
-        /// Panics if the `parent_id` does not exist, or if the node `parent_id ` has the child already set.
-        pub fn add_node(&mut self, parent_id: usize, key: u32, is_left: bool) -> usize {
-            assert!(
-                parent_id < self.nodes.len(),
-                "Parent node id does not exist"
-            );
-            if is_left {
-                assert!(
-                    self.nodes[parent_id].id_left.is_none(),
-                    "Parent node has the child already set"
-                );
-            } else {
-                assert!(
-                    self.nodes[parent_id].id_right.is_none(),
-                    "Parent node has the right child already set"
-                );
+        /// Panics if the `parent_id` does not exist (or it is a free slot), or if the node `parent_id ` has the child already set.
+        pub fn add_node(&mut self, parent_id: usize, key: K, is_left: bool) -> usize {
+            self.try_add_node(parent_id, key, is_left)
+                .unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        /// Same as `add_node`, but returns an error instead of panicking.
+        /// The tree is not modified if an error is returned.
+        pub fn try_add_node(&mut self, parent_id: usize, key: K, is_left: bool) -> Result<usize, TreeError> {
+            if !self.is_live(parent_id) {
+                return Err(TreeError::MissingParent { parent_id });
+            }
+            let parent = &self.nodes[parent_id];
+            let child_id_opt = if is_left { parent.id_left } else { parent.id_right };
+            if let Some(child_id) = child_id_opt {
+                return Err(TreeError::OccupiedSlot { parent_id, is_left, child_id });
             }
 
-            let child_id = self.nodes.len();
-            self.nodes.push(Node::new(key));
+            let child_id = match self.free_ids.pop() {
+                Some(free_id) => {
+                    self.nodes[free_id] = Node::new(key);
+                    self.is_free[free_id] = false;
+                    free_id
+                }
+                None => {
+                    self.nodes.push(Node::new(key));
+                    self.nodes.len() - 1
+                }
+            };
 
             let child = if is_left {
                 &mut self.nodes[parent_id].id_left
@@ -91,34 +157,311 @@ pub mod trees {
 
             *child = Some(child_id);
 
-            child_id
+            Ok(child_id)
         }
 
-        /// Returns the sum of all the keys in the tree
-        pub fn sum(&self) -> u32 {
-            self.rec_sum(Some(0))
+        /// # Returns
+        /// `true` iff `id` is a slot of `nodes` and it is not free
+        fn is_live(&self, id: usize) -> bool {
+            id < self.nodes.len() && !self.is_free.get(id).copied().unwrap_or(false)
+        }
+    }
+
+    /// # Node deletion and arena compaction
+    /// Removed nodes leave a free slot in `nodes`: their ids are kept in a free list and reused by `add_node`.
+    /// A free slot keeps its stale key, so it is not a node of the tree: `get_node` returns `None` for it,
+    /// and the methods that take a node id reject it like an id that is out of range.
+    impl<K> Tree<K> {
+
+        /// # Returns
+        /// the number of nodes in the tree (free slots excluded)
+        pub fn node_count(&self) -> usize {
+            self.nodes.len() - self.free_ids.len()
+        }
+
+        /// # Returns
+        /// the ids of the free slots of `nodes`
+        pub fn free_ids(&self) -> &[usize] {
+            &self.free_ids
+        }
+
+        /// Marks the slot `id` as free. The node must already be detached from the tree.
+        pub(crate) fn release_slot(&mut self, id: usize) {
+            self.nodes[id].id_left = None;
+            self.nodes[id].id_right = None;
+            self.free_ids.push(id);
+            if self.is_free.len() < self.nodes.len() {
+                self.is_free.resize(self.nodes.len(), false);
+            }
+            self.is_free[id] = true;
+        }
+
+        /// Removes the left (iff `is_left` is `true`) or the right subtree of the node `parent_id`.
+        ///
+        /// # Returns
+        /// the number of removed nodes (0 if the child does not exist)
+        ///
+        /// # Panics
+        /// Panics if the `parent_id` does not exist (or it is a free slot).
+        pub fn detach_child(&mut self, parent_id: usize, is_left: bool) -> usize {
+            self.try_detach_child(parent_id, is_left)
+                .unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        /// Same as `detach_child`, but returns an error instead of panicking
+        pub fn try_detach_child(&mut self, parent_id: usize, is_left: bool) -> Result<usize, TreeError> {
+            if !self.is_live(parent_id) {
+                return Err(TreeError::MissingParent { parent_id });
+            }
+            let parent = &mut self.nodes[parent_id];
+            let child_id_opt = if is_left { parent.id_left.take() } else { parent.id_right.take() };
+
+            let mut removed_count = 0;
+            let mut stack: Vec<usize> = child_id_opt.into_iter().collect();
+            while let Some(curr_id) = stack.pop() {
+                let curr_node = &self.nodes[curr_id];
+                stack.extend(curr_node.id_left);
+                stack.extend(curr_node.id_right);
+                self.release_slot(curr_id);
+                removed_count += 1;
+            }
+
+            Ok(removed_count)
+        }
+
+        /// Removes the node `id` together with all its descendants.
+        ///
+        /// # Returns
+        /// the number of removed nodes
+        ///
+        /// # Panics
+        /// Panics if `id` is the root (a `Tree` always has a root) or it is not a node of the tree.
+        pub fn remove_subtree(&mut self, id: usize) -> usize {
+            self.try_remove_subtree(id)
+                .unwrap_or_else(|error| panic!("{}", error))
         }
 
-        /// A private recursive function that computes the sum of
-        /// nodes in the subtree rooted at `node_id`.
-        fn rec_sum(&self, node_id: Option<usize>) -> u32 {
-            if let Some(id) = node_id {
-                assert!(id < self.nodes.len(), "Node id is out of range");
-                let node = &self.nodes[id];
+        /// Same as `remove_subtree`, but returns an error instead of panicking.
+        ///
+        /// The nodes do not store their parent, so finding it takes `O(n)` time (on top of the size of the removed subtree):
+        /// `detach_child` takes only the size of the subtree, when the parent is known.
+        pub fn try_remove_subtree(&mut self, id: usize) -> Result<usize, TreeError> {
+            if id == 0 {
+                return Err(TreeError::RootRemoval);
+            }
+            if !self.is_live(id) {
+                return Err(TreeError::MissingNode { id });
+            }
+
+            // the parent is found by scanning the slots: free slots have no children, so they never match
+            let (parent_id, is_left) = self
+                .nodes
+                .iter()
+                .enumerate()
+                .find_map(|(parent_id, node)| {
+                    if node.id_left == Some(id) {
+                        Some((parent_id, true))
+                    } else if node.id_right == Some(id) {
+                        Some((parent_id, false))
+                    } else {
+                        None
+                    }
+                })
+                .ok_or(TreeError::MissingNode { id })?;
+
+            self.try_detach_child(parent_id, is_left)
+        }
 
-                let sum_left = self.rec_sum(node.id_left);
-                let sum_right = self.rec_sum(node.id_right);
+        /// Renumbers the nodes in preorder (so the root keeps id 0), removing all the free slots.
+        ///
+        /// # Returns
+        /// the mapping from the old ids to the new ones: `mapping[old_id]` is `None` iff `old_id` was not in the tree
+        pub fn compact(&mut self) -> Vec<Option<usize>> {
+            let mut mapping: Vec<Option<usize>> = vec![None; self.nodes.len()];
+            let order: Vec<usize> = self.preorder().map(|(id, _)| id).collect();
+            for (new_id, old_id) in order.iter().enumerate() {
+                mapping[*old_id] = Some(new_id);
+            }
 
-                return sum_left + sum_right + node.key;
+            let mut old_nodes: Vec<Option<Node<K>>> = std::mem::take(&mut self.nodes).into_iter().map(Some).collect();
+            for old_id in order {
+                let mut node = old_nodes[old_id].take().unwrap();
+                node.id_left = node.id_left.and_then(|id| mapping[id]);
+                node.id_right = node.id_right.and_then(|id| mapping[id]);
+                self.nodes.push(node);
             }
+            self.free_ids.clear();
+            self.is_free.clear();
+
+            mapping
+        }
+    }
+
+    impl<K: Copy + Default + Add<Output = K>> Tree<K> {
+        /// Returns the sum of all the keys in the tree
+        pub fn sum(&self) -> K {
+            self.helper_sum(0)
+        }
+
+        /// Returns the sum of all the keys in the subtree rooted at `node_id`
+        pub fn sum_at(&self, node_id: usize) -> K {
+            assert!(self.is_live(node_id), "Node id is out of range or a free slot");
+            self.helper_sum(node_id)
+        }
 
-            0
+        /// Computes the sum of the nodes in the subtree rooted at `root_id`, bottom-up with `fold_postorder_at`.
+        fn helper_sum(&self, root_id: usize) -> K {
+            self.fold_postorder_at(root_id, |node, sum_left, sum_right| {
+                sum_left.unwrap_or_default() + sum_right.unwrap_or_default() + node.key
+            })
+            .unwrap_or_default()
         }
     }
 }
 
 
 
+#[cfg(test)]
+mod deletion_tests {
+    use super::*;
+    use test_support::perfect_tree;
+    use trees::*;
+
+    #[test]
+    fn test_detach_child() {
+        let mut tree = perfect_tree();
+
+        assert_eq!(tree.detach_child(0, true), 3);
+        assert_eq!(tree.to_level_order(), "[1,null,3,6,7]");
+        assert_eq!(tree.node_count(), 4);
+        assert_eq!(tree.sum(), 17);
+
+        // the child does not exist anymore
+        assert_eq!(tree.detach_child(0, true), 0);
+    }
+
+    #[test]
+    fn test_remove_subtree() {
+        let mut tree = perfect_tree();
+
+        assert_eq!(tree.remove_subtree(5), 1);
+        assert_eq!(tree.remove_subtree(2), 2);
+        assert_eq!(tree.to_level_order(), "[1,2,null,4,5]");
+    }
+
+    #[test]
+    #[should_panic(expected = "The root cannot be removed")]
+    fn test_remove_root() {
+        let mut tree = perfect_tree();
+        tree.remove_subtree(0);
+    }
+
+    #[test]
+    fn test_ids_are_reused() {
+        let mut tree = perfect_tree();
+        tree.remove_subtree(1);
+
+        let mut free_ids = tree.free_ids().to_vec();
+        free_ids.sort_unstable();
+        assert_eq!(free_ids, vec![1, 3, 4]);
+
+        let new_id = tree.add_node(0, 8, true);
+        assert!([1, 3, 4].contains(&new_id));
+        tree.add_node(new_id, 9, true);
+        tree.add_node(new_id, 10, false);
+        assert!(tree.free_ids().is_empty());
+        assert_eq!(tree.nodes.len(), 7);
+        assert_eq!(tree.to_level_order(), "[1,8,3,9,10,6,7]");
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut tree = perfect_tree();
+        tree.remove_subtree(1);
+        tree.remove_subtree(5);
+
+        let mapping = tree.compact();
+
+        assert_eq!(mapping, vec![Some(0), None, Some(1), None, None, None, Some(2)]);
+        assert_eq!(tree.nodes.len(), 3);
+        assert!(tree.free_ids().is_empty());
+        assert_eq!(tree.get_node(1).unwrap().key, 3);
+        assert_eq!(tree.get_node(1).unwrap().id_right, Some(2));
+        assert_eq!(tree.to_level_order(), "[1,null,3,null,7]");
+    }
+
+    #[test]
+    fn test_try_add_node() {
+        let mut tree = perfect_tree();
+
+        assert_eq!(tree.try_add_node(7, 8, true), Err(TreeError::MissingParent { parent_id: 7 }));
+        assert_eq!(tree.try_add_node(1, 8, false), Err(TreeError::OccupiedSlot { parent_id: 1, is_left: false, child_id: 4 }));
+        assert_eq!(tree.nodes.len(), 7);
+
+        assert_eq!(tree.try_add_node(3, 8, false), Ok(7));
+        assert_eq!(tree.to_level_order(), "[1,2,3,4,5,6,7,null,8]");
+    }
+
+    #[test]
+    fn test_try_remove() {
+        let mut tree = perfect_tree();
+
+        assert_eq!(tree.try_remove_subtree(0), Err(TreeError::RootRemoval));
+        assert_eq!(tree.try_detach_child(9, true), Err(TreeError::MissingParent { parent_id: 9 }));
+
+        assert_eq!(tree.try_remove_subtree(1), Ok(3));
+        assert_eq!(tree.try_remove_subtree(1), Err(TreeError::MissingNode { id: 1 }));
+        assert_eq!(tree.try_remove_subtree(3), Err(TreeError::MissingNode { id: 3 }));
+        assert_eq!(tree.node_count(), 4);
+    }
+
+    #[test]
+    fn test_free_slots_are_not_nodes() {
+        let mut tree = perfect_tree();
+        tree.remove_subtree(1);
+
+        assert!(tree.get_node(1).is_none());
+        assert!(tree.get_node(3).is_none());
+        assert_eq!(tree.preorder_at(3).count(), 0);
+        assert_eq!(tree.get_node(2).unwrap().key, 3);
+
+        // a reused slot is a node again
+        let new_id = tree.add_node(0, 8, true);
+        assert_eq!(tree.get_node(new_id).unwrap().key, 8);
+    }
+
+    #[test]
+    fn test_freed_ids_are_not_parents() {
+        let mut tree: Tree = Tree::from_level_order("[1,2,3,4]").unwrap();
+        tree.remove_subtree(1);
+
+        // the slots 1 and 3 are free: they cannot get children, and the tree is not modified
+        assert_eq!(tree.try_add_node(1, 9, true), Err(TreeError::MissingParent { parent_id: 1 }));
+        assert_eq!(tree.try_add_node(3, 9, false), Err(TreeError::MissingParent { parent_id: 3 }));
+        assert_eq!(tree.try_detach_child(1, true), Err(TreeError::MissingParent { parent_id: 1 }));
+        assert_eq!(tree.to_level_order(), "[1,null,3]");
+        assert_eq!(tree.node_count(), 2);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Parent node id 1 does not exist")]
+    fn test_add_node_freed_parent() {
+        let mut tree = perfect_tree();
+        tree.remove_subtree(1);
+        tree.add_node(1, 9, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Parent node 0 has the left child already set")]
+    fn test_add_node_occupied_slot() {
+        let mut tree = perfect_tree();
+        tree.add_node(0, 8, true);
+    }
+}
+
+
+
 
 
 
@@ -126,60 +469,261 @@ pub mod trees {
 /// # Optional utility: tree visualizer
 /// 
 /// implementation of some methods to graphically visualize the tree in a web browser
+/// 
+/// The tree can also be exported as a Mermaid diagram, and serialized to (and parsed from) JSON.
 mod visualizer {
 
-    use urlencoding::encode;
+    use crate::binary_tree;
+    use crate::binary_tree::BinaryTree;
     use crate::trees;
+    use crate::validation::InvalidTreeError;
+    use std::collections::HashSet;
+    use std::fmt;
+    use std::fmt::Display;
+    use std::str::FromStr;
+
+    /// Direction of the edges in the layout computed by Graphviz (the `rankdir` attribute)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RankDir {
+        TopBottom,
+        LeftRight,
+        BottomTop,
+        RightLeft,
+    }
 
-    impl trees::Tree {
+    impl RankDir {
+        fn as_dot(&self) -> &'static str {
+            match self {
+                RankDir::TopBottom => "TB",
+                RankDir::LeftRight => "LR",
+                RankDir::BottomTop => "BT",
+                RankDir::RightLeft => "RL",
+            }
+        }
+    }
 
-        /// useful to visualize the tree using Graphviz
-        /// # Returns 
-        /// the DOT representation of the tree,
-        pub fn to_dot(&self) -> String {
-            let mut dot = String::from("digraph Tree {\n");
+    /// computes the label of a node from its id and key
+    type LabelFn<'a, K> = Box<dyn Fn(usize, &K) -> String + 'a>;
+
+    /// Options for `to_dot_with`, built by chaining the setters on `DotOptions::new()`, e.g.
+    /// `DotOptions::new().highlight(report.offending_ids()).show_null_children(true)`.
+    /// The default options produce the same output as `to_dot`.
+    pub struct DotOptions<'a, K> {
+        label: Option<LabelFn<'a, K>>,
+        highlighted_ids: HashSet<usize>,
+        highlight_color: String,
+        show_null_children: bool,
+        rank_dir: Option<RankDir>,
+        same_rank_per_level: bool,
+    }
+
+    impl<K> Default for DotOptions<'_, K> {
+        fn default() -> Self {
+            Self {
+                label: None,
+                highlighted_ids: HashSet::new(),
+                highlight_color: String::from("tomato"),
+                show_null_children: false,
+                rank_dir: None,
+                same_rank_per_level: false,
+            }
+        }
+    }
+
+    impl<'a, K> DotOptions<'a, K> {
+
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// the label of every node is `label(id, key)` (by default `id=..\nvalue=..`)
+        pub fn label(mut self, label: impl Fn(usize, &K) -> String + 'a) -> Self {
+            self.label = Some(Box::new(label));
+            self
+        }
+
+        /// fills the nodes with the given ids with the highlight color
+        pub fn highlight(mut self, ids: impl IntoIterator<Item = usize>) -> Self {
+            self.highlighted_ids.extend(ids);
+            self
+        }
+
+        /// the Graphviz color of the highlighted nodes (by default `tomato`)
+        pub fn highlight_color(mut self, color: &str) -> Self {
+            self.highlight_color = String::from(color);
+            self
+        }
+
+        /// adds an invisible placeholder for the missing child of every node with only one child,
+        /// so that a left child is drawn on the left and a right child on the right
+        pub fn show_null_children(mut self, show: bool) -> Self {
+            self.show_null_children = show;
+            self
+        }
+
+        pub fn rank_dir(mut self, rank_dir: RankDir) -> Self {
+            self.rank_dir = Some(rank_dir);
+            self
+        }
+
+        /// forces the nodes with the same depth to be drawn on the same rank
+        pub fn same_rank_per_level(mut self, same_rank: bool) -> Self {
+            self.same_rank_per_level = same_rank;
+            self
+        }
+    }
 
-            // Traverse the tree and construct the DOT representation
-            self.rec_to_dot(0, &mut dot);
+    /// A step of `write_dot`, on a node with its DOT id and its depth
+    enum DotStep<N> {
+        /// write the node (and the edge from its parent, if any), then its left subtree
+        Visit(N, Option<usize>, usize),
+        /// write the right subtree of the node (or its placeholder)
+        RightSide(N, usize, usize),
+    }
 
-            dot.push_str("}\n");
-            
-            dot
+    /// Writes the DOT representation of any `BinaryTree`, with the style given by `options`.
+    ///
+    /// A node is named after its id (`BinaryTree::node_id`) or, for a tree without ids (`TreeNode`), after its position in preorder.
+    /// The nodes are visited in preorder with an explicit stack instead of recursion, so that a deep tree does not overflow the stack.
+    pub fn write_dot<T: BinaryTree>(tree: &T, options: &DotOptions<T::Key>) -> String
+    where
+        T::Key: Display,
+    {
+        let mut dot = String::from("digraph Tree {\n");
+
+        if let Some(rank_dir) = options.rank_dir {
+            dot.push_str(&format!("  rankdir={};\n", rank_dir.as_dot()));
+        }
+        if options.show_null_children {
+            // keeps the edges (and so the children) in the order they are written
+            dot.push_str("  ordering=out;\n");
         }
 
-        fn rec_to_dot(&self, node_id: usize, dot: &mut String) {
-            if let Some(node) = self.get_node(node_id) {
-                
-                // print the dot line with the information about the current node
-                dot.push_str(&format!("  id{} [label=\"id={}\nvalue={}\"];\n", node_id, node_id, node.key));
-                
-                // print the edge to the left (if it exists) and its subtree
-                if let Some(left_id) = node.id_left {
-                    dot.push_str(&format!("  id{} -> id{};\n", node_id, left_id));
-                    self.rec_to_dot(left_id, dot);
+        // the DOT ids of the nodes of every depth: the preorder visits each level from left to right
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        let mut next_preorder_id = 0;
+
+        let mut steps: Vec<DotStep<T::Node<'_>>> = tree.root().map(|root| DotStep::Visit(root, None, 0)).into_iter().collect();
+        while let Some(step) = steps.pop() {
+            match step {
+                DotStep::Visit(node, parent_id, depth) => {
+                    let node_id = tree.node_id(node).unwrap_or(next_preorder_id);
+                    next_preorder_id += 1;
+
+                    if levels.len() == depth {
+                        levels.push(Vec::new());
+                    }
+                    levels[depth].push(node_id);
+
+                    // the edge from the parent comes right before the subtree of the node
+                    if let Some(parent_id) = parent_id {
+                        dot.push_str(&format!("  id{} -> id{};\n", parent_id, node_id));
+                    }
+
+                    // print the dot line with the information about the current node
+                    let label = match &options.label {
+                        Some(label) => label(node_id, tree.key(node)),
+                        None => format!("id={}\nvalue={}", node_id, tree.key(node)),
+                    };
+                    let style = if options.highlighted_ids.contains(&node_id) {
+                        format!(", style=filled, fillcolor=\"{}\"", options.highlight_color)
+                    } else {
+                        String::new()
+                    };
+                    dot.push_str(&format!("  id{} [label=\"{}\"{}];\n", node_id, label.replace('"', "\\\""), style));
+
+                    // the right side is written after the whole left subtree
+                    steps.push(DotStep::RightSide(node, node_id, depth));
+                    if let Some(left) = tree.left(node) {
+                        steps.push(DotStep::Visit(left, Some(node_id), depth + 1));
+                    } else if options.show_null_children && tree.right(node).is_some() {
+                        dot.push_str(&format!("  null{}L [shape=point, style=invis];\n", node_id));
+                        dot.push_str(&format!("  id{} -> null{}L [style=invis];\n", node_id, node_id));
+                    }
                 }
-                
-                // print the edge to the right (if it exists) and its subtree
-                if let Some(right_id) = node.id_right {
-                    dot.push_str(&format!("  id{} -> id{};\n", node_id, right_id));
-                    self.rec_to_dot(right_id, dot);
+                DotStep::RightSide(node, node_id, depth) => {
+                    if let Some(right) = tree.right(node) {
+                        steps.push(DotStep::Visit(right, Some(node_id), depth + 1));
+                    } else if options.show_null_children && tree.left(node).is_some() {
+                        dot.push_str(&format!("  null{}R [shape=point, style=invis];\n", node_id));
+                        dot.push_str(&format!("  id{} -> null{}R [style=invis];\n", node_id, node_id));
+                    }
                 }
             }
         }
-        
+
+        if options.same_rank_per_level {
+            for level in levels {
+                let ids: Vec<String> = level.iter().map(|node_id| format!("id{};", node_id)).collect();
+                dot.push_str(&format!("  {{ rank=same; {} }}\n", ids.join(" ")));
+            }
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    impl<K: Display> trees::Tree<K> {
+
+        /// useful to visualize the tree using Graphviz
+        /// # Returns 
+        /// the DOT representation of the tree,
+        pub fn to_dot(&self) -> String {
+            self.to_dot_with(&DotOptions::new())
+        }
+
+        /// Same as `to_dot`, with the style given by `options`
+        pub fn to_dot_with(&self, options: &DotOptions<K>) -> String {
+            write_dot(self, options)
+        }
+
         /// # Returns
         /// A string representing the URL to visualize the tree in a web browser
         pub fn get_visualization_url(&self)-> String {
-            let dot_content = self.to_dot();
-            let encoded_dot = encode(&dot_content);
-            let edotor_url = format!(
-                "https://edotor.net/?engine=dot#{}",
-                encoded_dot
-            );
-            edotor_url
+            binary_tree::get_visualization_url(self)
+        }
+
+        /// useful to look at the tree without a browser (e.g. in the logs of the tests)
+        /// # Returns
+        /// a drawing of the tree with box-drawing characters, one node per line in preorder.
+        /// Every line shows the key and the id of a node, and the children are marked with `L` or `R`:
+        /// ```text
+        /// 10 (id 0)
+        /// ├── L: 5 (id 1)
+        /// │   └── R: 7 (id 3)
+        /// └── R: 20 (id 2)
+        /// ```
+        pub fn to_ascii(&self) -> String {
+            let mut ascii = String::new();
+
+            // the nodes still to be drawn, with the prefix of the lines of their children and the beginning of their own line
+            let mut stack: Vec<(usize, String, String)> = vec![(0, String::new(), String::new())];
+
+            while let Some((node_id, prefix, line_start)) = stack.pop() {
+                let Some(node) = self.get_node(node_id) else {
+                    continue;
+                };
+                ascii.push_str(&format!("{}{} (id {})\n", line_start, node.key, node_id));
+
+                let children: Vec<(&str, usize)> = [("L", node.id_left), ("R", node.id_right)]
+                    .into_iter()
+                    .filter_map(|(side, child_id_opt)| Some((side, child_id_opt?)))
+                    .collect();
+
+                // pushed in reverse order, so that the left child is drawn first
+                for (index, (side, child_id)) in children.iter().enumerate().rev() {
+                    let is_last = index == children.len() - 1;
+                    let (branch, indent) = if is_last { ("└── ", "    ") } else { ("├── ", "│   ") };
+                    stack.push((*child_id, format!("{}{}", prefix, indent), format!("{}{}{}: ", prefix, branch, side)));
+                }
+            }
+
+            ascii
         }
 
-        /// prints the URL to visualize the tree, along with some additional text (printed in an atomic way before the URL)
+        /// prints the URL to visualize the tree, along with some additional text (printed in an atomic way before the URL).
+        /// The tree is also drawn with `to_ascii()`, so it can be read directly in the terminal.
         /// # Arguments
         /// `additional_text`: additional text to print before the URL (useful to recognize the tree between many)
         pub fn print_visualization_url(&self, additional_text: &str) {
@@ -187,6 +731,7 @@ mod visualizer {
             output.push_str("=================================\n");
             output.push_str(additional_text);
             output.push('\n');
+            output.push_str(&self.to_ascii());
             output.push_str("Put this URL inside a browser to view the tree:\n");
             output.push_str(&self.get_visualization_url());
             output.push('\n');
@@ -196,335 +741,961 @@ mod visualizer {
         
     }
 
-}
 
+    /// escapes the characters that would end a quoted label in Mermaid
+    fn escape_mermaid(text: &str) -> String {
+        text.replace('"', "#quot;")
+    }
 
+    impl<K: Display> trees::Tree<K> {
 
+        /// useful to put the tree in Markdown notes (e.g. in a PR description), where Mermaid diagrams are rendered
+        /// # Returns
+        /// the Mermaid flowchart of the tree, with the same labels as `to_dot` and the edges marked with `L` or `R`
+        pub fn to_mermaid(&self) -> String {
+            let mut mermaid = String::from("graph TD\n");
+
+            for (node_id, node) in self.preorder() {
+                let label = format!("id={}<br/>value={}", node_id, node.key);
+                mermaid.push_str(&format!("  id{}[\"{}\"]\n", node_id, escape_mermaid(&label)));
+
+                for (side, child_id_opt) in [("L", node.id_left), ("R", node.id_right)] {
+                    if let Some(child_id) = child_id_opt {
+                        mermaid.push_str(&format!("  id{} -->|{}| id{}\n", node_id, side, child_id));
+                    }
+                }
+            }
 
+            mermaid
+        }
 
+        /// # Returns
+        /// the JSON representation of the tree, with every slot of `nodes` (in order) and the free list:
+        /// ```text
+        /// {"nodes":[
+        ///   {"id":0,"key":10,"left":1,"right":null},
+        ///   {"id":1,"key":5,"left":null,"right":null}
+        /// ],"free_ids":[]}
+        /// ```
+        /// A key is written as a JSON number if it looks like one, otherwise as a JSON string.
+        pub fn to_json(&self) -> String {
+            let id_to_json = |id_opt: Option<usize>| id_opt.map_or(String::from("null"), |id| id.to_string());
+
+            let nodes: Vec<String> = self
+                .nodes
+                .iter()
+                .enumerate()
+                .map(|(node_id, node)| {
+                    let key = node.key.to_string();
+                    let key = if is_json_number(&key) { key } else { quote_json(&key) };
+                    format!(
+                        "  {{\"id\":{},\"key\":{},\"left\":{},\"right\":{}}}",
+                        node_id,
+                        key,
+                        id_to_json(node.id_left),
+                        id_to_json(node.id_right)
+                    )
+                })
+                .collect();
+            let free_ids: Vec<String> = self.free_ids().iter().map(|id| id.to_string()).collect();
+
+            format!("{{\"nodes\":[\n{}\n],\"free_ids\":[{}]}}\n", nodes.join(",\n"), free_ids.join(","))
+        }
+    }
 
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum JsonError {
+        /// the input is not valid JSON: `expected` was expected at byte `position`
+        Syntax { position: usize, expected: &'static str },
+        /// a required field is missing, or it has the wrong type
+        InvalidField { field: &'static str },
+        /// the node at `index` of the `nodes` array has a different `id`
+        IdMismatch { index: usize, id: usize },
+        /// the key of the node `id` cannot be parsed
+        InvalidKey { id: usize, key: String },
+        /// the arrays and objects are nested more than `MAX_JSON_DEPTH` levels deep at byte `position`
+        TooDeep { position: usize },
+        /// the links do not form a tree
+        InvalidTree(InvalidTreeError),
+    }
 
-/// demo to show the print_visualization_url method
-fn main() {
-    println!("Hello, trees handson!");
+    impl fmt::Display for JsonError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                JsonError::Syntax { position, expected } => write!(f, "expected {} at position {}", expected, position),
+                JsonError::InvalidField { field } => write!(f, "the field '{}' is missing or has the wrong type", field),
+                JsonError::IdMismatch { index, id } => write!(f, "the node at index {} has id {}", index, id),
+                JsonError::InvalidKey { id, key } => write!(f, "invalid key '{}' of node {}", key, id),
+                JsonError::TooDeep { position } => write!(f, "nesting deeper than {} levels at position {}", MAX_JSON_DEPTH, position),
+                JsonError::InvalidTree(error) => write!(f, "{}", error),
+            }
+        }
+    }
 
-    // Create your binary tree
-    let mut tree = trees::Tree::with_root(10);
-    let _node_b = tree.add_node(0, 5, true);
-    let _node_c = tree.add_node(0, 15, false);
-    let _node_d = tree.add_node(_node_b, 3, true);
-    let _node_e = tree.add_node(_node_b, 7, false);
+    impl std::error::Error for JsonError {}
 
-    tree.print_visualization_url("This is a demo to show the print_visualization_url method.");
-}
+    impl<K: FromStr> trees::Tree<K> {
 
+        /// Parses a tree written by `to_json`. The result is checked with `validate()`.
+        pub fn from_json(input: &str) -> Result<Self, JsonError> {
+            let mut parser = JsonParser { input: input.as_bytes(), position: 0, depth: 0 };
+            let value = parser.parse_value()?;
+            parser.skip_whitespace();
+            if parser.position != input.len() {
+                return Err(JsonError::Syntax { position: parser.position, expected: "the end of the input" });
+            }
 
+            let nodes_value = value.field("nodes").and_then(JsonValue::as_array).ok_or(JsonError::InvalidField { field: "nodes" })?;
+            let free_ids_value = value.field("free_ids").and_then(JsonValue::as_array).ok_or(JsonError::InvalidField { field: "free_ids" })?;
 
+            let mut nodes = Vec::with_capacity(nodes_value.len());
+            for (index, node_value) in nodes_value.iter().enumerate() {
+                let id = node_value.field("id").and_then(JsonValue::as_id).ok_or(JsonError::InvalidField { field: "id" })?;
+                if id != index {
+                    return Err(JsonError::IdMismatch { index, id });
+                }
 
+                let key_text = node_value.field("key").and_then(JsonValue::as_text).ok_or(JsonError::InvalidField { field: "key" })?;
+                let key = key_text.parse().map_err(|_| JsonError::InvalidKey { id, key: key_text.to_string() })?;
 
+                let id_left = node_value.field("left").and_then(JsonValue::as_optional_id).ok_or(JsonError::InvalidField { field: "left" })?;
+                let id_right = node_value.field("right").and_then(JsonValue::as_optional_id).ok_or(JsonError::InvalidField { field: "right" })?;
 
+                nodes.push(trees::Node { key, id_left, id_right });
+            }
 
-/// # Exercise 1
-/// 
-/// Write a method to check if the binary tree is a Binary Search Tree.
-impl trees::Tree {
+            let free_ids = free_ids_value
+                .iter()
+                .map(JsonValue::as_id)
+                .collect::<Option<Vec<usize>>>()
+                .ok_or(JsonError::InvalidField { field: "free_ids" })?;
 
+            let tree = Self::from_raw_parts(nodes, free_ids);
+            tree.validate().map_err(JsonError::InvalidTree)?;
+            Ok(tree)
+        }
+    }
 
     /// # Returns
-    /// True iff the tree (rooted at id 0) is a binary search tree. False otherwise.
-    pub fn is_bst(&self) -> bool {
-        self.helper_rec_is_bst(Some(0)).0
+    /// true iff `text` is a number in JSON syntax
+    fn is_json_number(text: &str) -> bool {
+        let text = text.strip_prefix('-').unwrap_or(text);
+        let (mantissa, exponent) = match text.find(['e', 'E']) {
+            Some(index) => (&text[..index], Some(&text[index + 1..])),
+            None => (text, None),
+        };
+        let (integer, fraction) = match mantissa.split_once('.') {
+            Some((integer, fraction)) => (integer, Some(fraction)),
+            None => (mantissa, None),
+        };
+
+        let is_digits = |digits: &str| !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit());
+
+        is_digits(integer)
+            && (integer == "0" || !integer.starts_with('0'))
+            && fraction.is_none_or(is_digits)
+            && exponent.is_none_or(|exponent| is_digits(exponent.strip_prefix(['+', '-']).unwrap_or(exponent)))
     }
 
-
-    /// Helper recursive function for is_bst
-    /// 
     /// # Returns
-    /// A tuple `(is_bst, max, min)`,
-    /// 
-    /// where `max` is the maximum value in the subtree, and `min` is the minimum.
-    fn helper_rec_is_bst(&self, curr_root_id: Option<usize>) -> (bool, u32, u32) {
-        const NEG_INF: u32 = u32::MIN; // since we are dealing with u32, the value is actually 0
-        const INF: u32 = u32::MAX;
+    /// `text` as a JSON string, with quotes and escapes
+    fn quote_json(text: &str) -> String {
+        let mut quoted = String::from("\"");
+        for c in text.chars() {
+            match c {
+                '"' => quoted.push_str("\\\""),
+                '\\' => quoted.push_str("\\\\"),
+                '\n' => quoted.push_str("\\n"),
+                '\r' => quoted.push_str("\\r"),
+                '\t' => quoted.push_str("\\t"),
+                c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+                c => quoted.push(c),
+            }
+        }
+        quoted.push('"');
+        quoted
+    }
 
-        if curr_root_id.is_none() {
-            return (true, NEG_INF, INF);
+    enum JsonValue {
+        Null,
+        Bool,
+        /// the text of the number, as written in the input
+        Number(String),
+        String(String),
+        Array(Vec<JsonValue>),
+        Object(Vec<(String, JsonValue)>),
+    }
+
+    impl JsonValue {
+        fn field(&self, name: &str) -> Option<&JsonValue> {
+            match self {
+                JsonValue::Object(fields) => fields.iter().find(|(field, _)| field == name).map(|(_, value)| value),
+                _ => None,
+            }
         }
 
-        let root: &trees::Node = self.get_node(curr_root_id.unwrap()).unwrap(); // should always be Some(node), unless the tree is not valid
+        fn as_array(&self) -> Option<&Vec<JsonValue>> {
+            match self {
+                JsonValue::Array(values) => Some(values),
+                _ => None,
+            }
+        }
 
-        let (is_left_bst, max_left, min_left ) = self.helper_rec_is_bst(root.id_left);
-        let (is_right_bst, max_right, min_right) = self.helper_rec_is_bst(root.id_right);
+        fn as_id(&self) -> Option<usize> {
+            match self {
+                JsonValue::Number(text) => text.parse().ok(),
+                _ => None,
+            }
+        }
 
-        if !is_left_bst || !is_right_bst {
-            return (false, NEG_INF, INF);
+        /// `Some(None)` for `null`
+        fn as_optional_id(&self) -> Option<Option<usize>> {
+            match self {
+                JsonValue::Null => Some(None),
+                value => value.as_id().map(Some),
+            }
         }
 
-        let am_i_bst: bool = max_left <= root.key && root.key <= min_right;
+        /// the text of a number or a string
+        fn as_text(&self) -> Option<&str> {
+            match self {
+                JsonValue::Number(text) | JsonValue::String(text) => Some(text),
+                _ => None,
+            }
+        }
+    }
 
-        let new_max = max(root.key, max(max_left, max_right));
-        let new_min = min(root.key, min(min_left, min_right));
-        
-        return (am_i_bst, new_max, new_min);
-        
+    /// The maximum nesting of arrays and objects accepted by `from_json`.
+    /// The output of `to_json` is nested 3 levels deep (the tree, the `nodes` array and the nodes).
+    pub const MAX_JSON_DEPTH: usize = 32;
+
+    /// A minimal recursive-descent JSON parser.
+    /// The recursion is bounded by `MAX_JSON_DEPTH`, so a deeply nested input (e.g. `[[[[...`) is an error instead of a stack overflow.
+    struct JsonParser<'a> {
+        input: &'a [u8],
+        position: usize,
+        /// the number of arrays and objects that are being parsed
+        depth: usize,
     }
 
+    impl JsonParser<'_> {
+        fn skip_whitespace(&mut self) {
+            while self.input.get(self.position).is_some_and(|byte| byte.is_ascii_whitespace()) {
+                self.position += 1;
+            }
+        }
 
+        fn error(&self, expected: &'static str) -> JsonError {
+            JsonError::Syntax { position: self.position, expected }
+        }
 
-}
+        /// skips the whitespace and consumes `byte` if it is the next one
+        fn eat(&mut self, byte: u8) -> bool {
+            self.skip_whitespace();
+            if self.input.get(self.position) == Some(&byte) {
+                self.position += 1;
+                true
+            } else {
+                false
+            }
+        }
 
+        fn eat_literal(&mut self, literal: &str) -> bool {
+            if self.input[self.position..].starts_with(literal.as_bytes()) {
+                self.position += literal.len();
+                true
+            } else {
+                false
+            }
+        }
 
+        fn parse_value(&mut self) -> Result<JsonValue, JsonError> {
+            self.skip_whitespace();
+            match self.input.get(self.position) {
+                Some(b'{' | b'[') if self.depth == MAX_JSON_DEPTH => Err(JsonError::TooDeep { position: self.position }),
+                Some(b'{') => self.parse_nested(Self::parse_object),
+                Some(b'[') => self.parse_nested(Self::parse_array),
+                Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+                Some(b'-' | b'0'..=b'9') => self.parse_number(),
+                _ if self.eat_literal("null") => Ok(JsonValue::Null),
+                _ if self.eat_literal("true") || self.eat_literal("false") => Ok(JsonValue::Bool),
+                _ => Err(self.error("a value")),
+            }
+        }
 
-/// # Tests for exercise 1
-#[cfg(test)]
-mod ex_1_tests {
-    use super::*;
+        /// parses an array or an object with `parse`, one level deeper
+        fn parse_nested(&mut self, parse: fn(&mut Self) -> Result<JsonValue, JsonError>) -> Result<JsonValue, JsonError> {
+            self.depth += 1;
+            let result = parse(self);
+            self.depth -= 1;
+            result
+        }
 
-    fn build_example_bst() -> trees::Tree {
-        let mut tree = trees::Tree::with_root(20);
+        fn parse_object(&mut self) -> Result<JsonValue, JsonError> {
+            self.position += 1; // '{'
+            let mut fields = Vec::new();
+            if self.eat(b'}') {
+                return Ok(JsonValue::Object(fields));
+            }
+            loop {
+                self.skip_whitespace();
+                if self.input.get(self.position) != Some(&b'"') {
+                    return Err(self.error("a field name"));
+                }
+                let name = self.parse_string()?;
+                if !self.eat(b':') {
+                    return Err(self.error("':'"));
+                }
+                fields.push((name, self.parse_value()?));
 
-        // first level
-        tree.add_node(0, 10, true); // id 1
-        tree.add_node(0, 21, false); // id 2
+                if self.eat(b'}') {
+                    return Ok(JsonValue::Object(fields));
+                }
+                if !self.eat(b',') {
+                    return Err(self.error("',' or '}'"));
+                }
+            }
+        }
 
-        // second level
-        tree.add_node(1, 6, true); // id 3
-        tree.add_node(1, 15, false); // id 4
+        fn parse_array(&mut self) -> Result<JsonValue, JsonError> {
+            self.position += 1; // '['
+            let mut values = Vec::new();
+            if self.eat(b']') {
+                return Ok(JsonValue::Array(values));
+            }
+            loop {
+                values.push(self.parse_value()?);
 
-        tree.add_node(2, 20, true); // id 5
-        tree.add_node(2, 100, false); //id 6
+                if self.eat(b']') {
+                    return Ok(JsonValue::Array(values));
+                }
+                if !self.eat(b',') {
+                    return Err(self.error("',' or ']'"));
+                }
+            }
+        }
 
-        // third level
-        tree.add_node(3,1, true); // id 7
-        tree.add_node(3, 8, false); // id 8
+        fn parse_number(&mut self) -> Result<JsonValue, JsonError> {
+            let start = self.position;
+            while self.input.get(self.position).is_some_and(|byte| matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+                self.position += 1;
+            }
+            // the input is valid UTF-8 and the number is ASCII
+            let text = std::str::from_utf8(&self.input[start..self.position]).unwrap();
+            if !is_json_number(text) {
+                return Err(JsonError::Syntax { position: start, expected: "a number" });
+            }
+            Ok(JsonValue::Number(text.to_string()))
+        }
 
-        tree.add_node(4, 14, true); // id 9
-        tree.add_node(4, 18, false); // id 10
+        fn parse_string(&mut self) -> Result<String, JsonError> {
+            self.position += 1; // '"'
+            let mut bytes = Vec::new();
+            loop {
+                match self.input.get(self.position) {
+                    None => return Err(self.error("'\"'")),
+                    Some(b'"') => break,
+                    Some(b'\\') => {
+                        self.position += 1;
+                        let escaped = match self.input.get(self.position) {
+                            Some(b'"') => '"',
+                            Some(b'\\') => '\\',
+                            Some(b'/') => '/',
+                            Some(b'b') => '\u{8}',
+                            Some(b'f') => '\u{c}',
+                            Some(b'n') => '\n',
+                            Some(b'r') => '\r',
+                            Some(b't') => '\t',
+                            Some(b'u') => {
+                                let code = self
+                                    .input
+                                    .get(self.position + 1..self.position + 5)
+                                    .and_then(|hex| u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok())
+                                    .and_then(char::from_u32)
+                                    .ok_or(self.error("a unicode escape"))?;
+                                self.position += 4;
+                                code
+                            }
+                            _ => return Err(self.error("an escape sequence")),
+                        };
+                        let mut buffer = [0; 4];
+                        bytes.extend_from_slice(escaped.encode_utf8(&mut buffer).as_bytes());
+                    }
+                    Some(&byte) => bytes.push(byte),
+                }
+                self.position += 1;
+            }
+            self.position += 1; // '"'
 
-        tree.add_node(6, 115, false); // id 11
+            // the input is valid UTF-8, and the string was split only at ASCII characters
+            Ok(String::from_utf8(bytes).unwrap())
+        }
+    }
 
-        // fourth level
-        tree.add_node(11, 115, false); // id 12
-        tree.add_node(11, 115, true);
+}
 
-        return tree;
+
+
+#[cfg(test)]
+mod visualizer_tests {
+    use super::*;
+    use trees::*;
+
+    #[test]
+    fn test_to_ascii() {
+        let tree: Tree = Tree::from_level_order("[10,5,20,null,7,15,30,6]").unwrap();
+
+        let expected = "\
+10 (id 0)
+├── L: 5 (id 1)
+│   └── R: 7 (id 3)
+│       └── L: 6 (id 6)
+└── R: 20 (id 2)
+    ├── L: 15 (id 4)
+    └── R: 30 (id 5)
+";
+        assert_eq!(tree.to_ascii(), expected);
     }
 
     #[test]
-    fn test_is_bst_1() {
-        // let's check if this tree is recognized as a bst
-        let mut ex_tree = build_example_bst();
-        assert!(ex_tree.is_bst());
+    fn test_to_dot() {
+        let tree: Tree = Tree::from_level_order("[2,1]").unwrap();
+        assert_eq!(tree.to_dot(), "digraph Tree {\n  id0 [label=\"id=0\nvalue=2\"];\n  id0 -> id1;\n  id1 [label=\"id=1\nvalue=1\"];\n}\n");
+        assert_eq!(tree.to_dot_with(&visualizer::DotOptions::new()), tree.to_dot());
+    }
 
-        // let's add a node to this tree so that it is not a bst anymore
-        ex_tree.add_node(12, 13, false);
+    #[test]
+    fn test_to_dot_deep_chain() {
+        use test_support::{chain, CHAIN_LEN};
+
+        let tree = chain(false, |i| i);
+
+        // the DOT writer does not recurse on the depth of the tree
+        assert_eq!(tree.to_dot().matches(" -> ").count(), CHAIN_LEN as usize - 1);
+    }
+
+    #[test]
+    fn test_to_dot_with_options() {
+        use visualizer::{DotOptions, RankDir};
+
+        let tree: Tree = Tree::from_level_order("[5,3,9,null,7]").unwrap();
+        let violations = tree.check_bst().offending_ids();
+
+        let options = DotOptions::new()
+            .label(|id, key: &u32| format!("{} \"#{}\"", key, id))
+            .highlight(violations)
+            .highlight_color("yellow")
+            .show_null_children(true)
+            .rank_dir(RankDir::LeftRight)
+            .same_rank_per_level(true);
+        let dot = tree.to_dot_with(&options);
 
         if PRINT_TREE {
-            ex_tree.print_visualization_url("Tree inside test_is_bst_1:");
+            println!("{}", dot);
         }
 
-        assert!(ex_tree.is_bst() == false , "this should not be a bst!");
+        let expected = "\
+digraph Tree {
+  rankdir=LR;
+  ordering=out;
+  id0 [label=\"5 \\\"#0\\\"\", style=filled, fillcolor=\"yellow\"];
+  id0 -> id1;
+  id1 [label=\"3 \\\"#1\\\"\"];
+  null1L [shape=point, style=invis];
+  id1 -> null1L [style=invis];
+  id1 -> id3;
+  id3 [label=\"7 \\\"#3\\\"\", style=filled, fillcolor=\"yellow\"];
+  id0 -> id2;
+  id2 [label=\"9 \\\"#2\\\"\"];
+  { rank=same; id0; }
+  { rank=same; id1; id2; }
+  { rank=same; id3; }
+}
+";
+        assert_eq!(dot, expected);
     }
 
-    // let's try another tree that is not a bst
     #[test]
-    fn test_is_bst_2() {
-        let mut t2: trees::Tree = build_example_bst();
-        t2.add_node(12, 80, true);
+    fn test_to_ascii_single_node() {
+        let tree = Tree::with_root("root");
+        assert_eq!(tree.to_ascii(), "root (id 0)\n");
+    }
 
-        if PRINT_TREE {
-            t2.print_visualization_url("Tree inside test_is_bst_2:");
+    #[test]
+    fn test_to_mermaid() {
+        let tree: Tree<String> = Tree::from_level_order("[a,null,\"b\"]").unwrap();
+
+        let expected = "\
+graph TD
+  id0[\"id=0<br/>value=a\"]
+  id0 -->|R| id1
+  id1[\"id=1<br/>value=#quot;b#quot;\"]
+";
+        assert_eq!(tree.to_mermaid(), expected);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let mut tree: Tree = Tree::from_level_order("[10,5,20,null,7]").unwrap();
+        tree.remove_subtree(3);
+
+        let expected = "\
+{\"nodes\":[
+  {\"id\":0,\"key\":10,\"left\":1,\"right\":2},
+  {\"id\":1,\"key\":5,\"left\":null,\"right\":null},
+  {\"id\":2,\"key\":20,\"left\":null,\"right\":null},
+  {\"id\":3,\"key\":7,\"left\":null,\"right\":null}
+],\"free_ids\":[3]}
+";
+        assert_eq!(tree.to_json(), expected);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let mut tree: Tree = Tree::from_level_order("[1,2,3,4,5,6,7]").unwrap();
+        tree.remove_subtree(1);
+        tree.add_node(0, 8, true);
+        tree.remove_subtree(6);
+
+        let parsed: Tree = Tree::from_json(&tree.to_json()).unwrap();
+        assert_eq!(parsed.to_json(), tree.to_json());
+        assert_eq!(parsed.free_ids(), tree.free_ids());
+        assert_eq!(parsed.to_level_order(), tree.to_level_order());
+
+        let tree: Tree<i64> = Tree::from_level_order("[-3,0,1000000000000]").unwrap();
+        assert_eq!(Tree::<i64>::from_json(&tree.to_json()).unwrap().to_level_order(), "[-3,0,1000000000000]");
+
+        // the keys that do not look like numbers are written as strings
+        let mut tree = Tree::with_root(String::from("say \"hi\"\n\\"));
+        tree.add_node(0, String::from("12"), true);
+        tree.add_node(0, String::from("é"), false);
+        let json = tree.to_json();
+        assert!(json.contains("\"key\":12,"));
+        assert!(json.contains("\"key\":\"say \\\"hi\\\"\\n\\\\\","));
+
+        let parsed: Tree<String> = Tree::from_json(&json).unwrap();
+        assert_eq!(parsed.to_json(), json);
+        assert_eq!(parsed.get_node(0).unwrap().key, "say \"hi\"\n\\");
+    }
+
+    #[test]
+    fn test_from_json_whitespace_and_escapes() {
+        let json = " { \"free_ids\" : [ ] , \"nodes\" : [ { \"right\" : null , \"left\" : null , \"key\" : \"\\u0041\\/\" , \"id\" : 0 } ] } ";
+        let tree: Tree<String> = Tree::from_json(json).unwrap();
+        assert_eq!(tree.get_node(0).unwrap().key, "A/");
+    }
+
+    #[test]
+    fn test_from_json_errors() {
+        use visualizer::JsonError;
+
+        assert_eq!(Tree::<u32>::from_json("{\"nodes\":[").err(), Some(JsonError::Syntax { position: 10, expected: "a value" }));
+        assert_eq!(Tree::<u32>::from_json("{\"nodes\":[]} x").err(), Some(JsonError::Syntax { position: 13, expected: "the end of the input" }));
+        assert_eq!(Tree::<u32>::from_json("{\"nodes\":[]}").err(), Some(JsonError::InvalidField { field: "free_ids" }));
+        assert_eq!(Tree::<u32>::from_json("{\"nodes\":[{\"id\":0}],\"free_ids\":[]}").err(), Some(JsonError::InvalidField { field: "key" }));
+        assert_eq!(
+            Tree::<u32>::from_json("{\"nodes\":[{\"id\":1,\"key\":1,\"left\":null,\"right\":null}],\"free_ids\":[]}").err(),
+            Some(JsonError::IdMismatch { index: 0, id: 1 })
+        );
+        assert_eq!(
+            Tree::<u32>::from_json("{\"nodes\":[{\"id\":0,\"key\":-1,\"left\":null,\"right\":null}],\"free_ids\":[]}").err(),
+            Some(JsonError::InvalidKey { id: 0, key: String::from("-1") })
+        );
+
+        // valid JSON, but the links do not form a tree
+        let error = Tree::<u32>::from_json("{\"nodes\":[{\"id\":0,\"key\":1,\"left\":0,\"right\":null}],\"free_ids\":[]}").err();
+        match error {
+            Some(JsonError::InvalidTree(invalid)) => assert_eq!(invalid.offending_ids(), vec![0]),
+            other => panic!("unexpected result: {:?}", other),
         }
 
-        assert!(t2.is_bst() == false , "this should not be a bst!");
+        assert_eq!(Tree::<u32>::from_json("{\"nodes\":[],\"free_ids\":[]}").err().map(|error| error.to_string()), Some(String::from("the tree is not valid:\n- the tree has no nodes")));
     }
-}
 
+    #[test]
+    fn test_from_json_too_deep() {
+        use visualizer::{JsonError, MAX_JSON_DEPTH};
 
+        // deep enough to overflow the stack of an unbounded recursive parser
+        let deep = "[".repeat(1_000_000);
+        assert_eq!(Tree::<u32>::from_json(&deep).err(), Some(JsonError::TooDeep { position: MAX_JSON_DEPTH }));
 
+        let nested = format!("{{\"nodes\":{}{}", "[".repeat(MAX_JSON_DEPTH), "]".repeat(MAX_JSON_DEPTH));
+        assert_eq!(Tree::<u32>::from_json(&nested).err(), Some(JsonError::TooDeep { position: MAX_JSON_DEPTH + 8 }));
 
+        // the limit is the maximum depth, not the maximum size
+        let mut tree = Tree::with_root(0);
+        for id in 0..999 {
+            tree.add_node(id, id as u32 + 1, false);
+        }
+        assert_eq!(Tree::<u32>::from_json(&tree.to_json()).unwrap().to_json(), tree.to_json());
+    }
+}
 
 
 
 
 
 
-/// # Exercise 2
-/// 
-/// Write a method to check if the binary tree is balanced.
-/// 
-/// A tree is considered balanced if, for each of its nodes, the heights of its left and right subtrees differ by at most one.
-/// 
-/// /// # Pseudocode:
-///
-/// ```
-/// #[cfg(doctest)]
-/// is_balanced(node) -> (bool, int) {
-///     if node is None {
-///         return (True, 0)
-///     }
-///
-///     let (is_balanced_left, height_left) = is_balanced(node.left);
-///     let (is_balanced_right, height_right) = is_balanced(node.right);
+
+/// # Optional utility: offline SVG and HTML export
 ///
-///     let am_i_balanced = is_balanced_left && is_balanced_right && abs(height_left - height_right) <= 1;
-///     let new_height = max(height_left, height_right) + 1;
+/// Unlike `print_visualization_url`, these functions need neither a browser with network access nor Graphviz:
+/// the layout is computed here, and the result is a standalone file.
 ///
-///     return (am_i_balanced, new_height);
-/// }
-/// ```
+/// The layout is the tidy drawing of Reingold and Tilford: the y coordinate of a node is its depth, and the subtrees
+/// of a node are drawn independently, then pushed as close as possible to each other, so that on every level the nodes
+/// are at least `COLUMN_WIDTH` apart. The parent is centered above its two children, and a single child is drawn half
+/// a column to the left (or to the right) of its parent. So every left subtree is on the left of its parent,
+/// every right subtree on the right, equal subtrees are drawn in the same way, and no two nodes overlap.
 ///
-impl trees::Tree {
+/// Comparing the subtrees needs only their contours (the leftmost and rightmost node of every level), and the contour
+/// of a subtree is built from the taller one of its children, so the layout takes `O(n log n)` time without recursion.
+mod svg_export {
 
-    pub fn is_balanced(&self) -> bool {
-        self.rec_helper_is_balanced(Some(0)).0
+    use crate::trees;
+    use std::cmp::{max, min};
+    use std::fmt::Display;
+    use std::fs;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    /// the minimum distance between the centers of two nodes of the same level
+    const COLUMN_WIDTH: usize = 48;
+    const LEVEL_HEIGHT: usize = 64;
+    const MARGIN: usize = 24;
+    const RADIUS: usize = 18;
+
+    /// escapes the characters with a special meaning in XML
+    fn escape_xml(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
     }
 
-    pub fn get_height(&self) -> u32 {
-        self.rec_helper_is_balanced(Some(0)).1
+    /// The leftmost and rightmost x of every level of a subtree, relative to its root.
+    /// The levels are stored from the deepest one, so that the level of a new root is pushed at the end,
+    /// and `shift` is added to every stored value, so that the whole contour is moved in `O(1)`.
+    struct Contour {
+        shift: i64,
+        levels: Vec<(i64, i64)>,
     }
 
-    /// Recursively checks if the binary tree is balanced.
-    ///
-    /// This function is used  (as a helper function) to recursively determine if the binary tree, rooted at the `curr_id_opt` node,
-    /// is balanced.
-    ///
-    /// # Arguments
-    ///
-    /// * `curr_id_opt` - The ID of the node to be considered as the root. Pass `None` to check the entire tree.
-    ///
-    /// # Returns
-    ///
-    /// A tuple `(bool, u32)` where the first element is `true` if the tree is balanced, and the second element
-    /// represents the height of the tree.
-    fn rec_helper_is_balanced(&self, curr_id_opt: Option<usize>) -> (bool, u32) {
-        if curr_id_opt.is_none(){
-            return (true, 0);
+    impl Contour {
+        fn leaf() -> Self {
+            Self { shift: 0, levels: vec![(0, 0)] }
         }
-        let curr_id = curr_id_opt.unwrap();
 
+        fn height(&self) -> usize {
+            self.levels.len()
+        }
+
+        /// # Returns
+        /// the leftmost and rightmost x of the level `depth` (0 for the root)
+        fn level(&self, depth: usize) -> (i64, i64) {
+            let (left, right) = self.levels[self.levels.len() - 1 - depth];
+            (left + self.shift, right + self.shift)
+        }
 
-        let curr_node_opt = self.get_node(curr_id);
-        if curr_node_opt.is_none() {
-            return (true, 0);
+        fn set_level(&mut self, depth: usize, (left, right): (i64, i64)) {
+            let index = self.levels.len() - 1 - depth;
+            self.levels[index] = (left - self.shift, right - self.shift);
         }
-        let curr_node = curr_node_opt.unwrap();
 
-        let (is_balanced_left, height_left) = self.rec_helper_is_balanced(curr_node.id_left);
-        let (is_balanced_right, height_right) = self.rec_helper_is_balanced(curr_node.id_right);
+        /// Adds a root at x 0 above the subtree (whose root must already be moved to its x relative to the new root)
+        fn push_root(&mut self) {
+            self.levels.push((-self.shift, -self.shift));
+        }
+    }
 
-        let am_i_balanced: bool = 
-            is_balanced_left &&
-            is_balanced_right &&
-            ( height_left.abs_diff(height_right) <= 1 )
-        ;
+    impl<K> trees::Tree<K> {
+
+        /// # Returns
+        /// the position `(x, depth)` of every node in the tidy layout: `positions[id]` is `None` iff `id` is not in the tree.
+        /// `x` is in half columns (so two nodes of the same level have x at least 2 apart), and the leftmost node has x 0.
+        pub fn layout(&self) -> Vec<Option<(usize, usize)>> {
+            // x of every node relative to its parent
+            let mut offsets = vec![0i64; self.nodes.len()];
+            let mut contours: Vec<Option<Contour>> = (0..self.nodes.len()).map(|_| None).collect();
+
+            // in postorder, the children are laid out before their parent
+            for (node_id, node) in self.postorder() {
+                let left = node.id_left.map(|left_id| (left_id, contours[left_id].take().unwrap()));
+                let right = node.id_right.map(|right_id| (right_id, contours[right_id].take().unwrap()));
+
+                let contour = match (left, right) {
+                    (None, None) => Contour::leaf(),
+                    (Some((child_id, mut child)), None) | (None, Some((child_id, mut child))) => {
+                        offsets[child_id] = if node.id_left == Some(child_id) { -1 } else { 1 };
+                        child.shift += offsets[child_id];
+                        child.push_root();
+                        child
+                    }
+                    (Some((left_id, mut left)), Some((right_id, mut right))) => {
+                        // the smallest even distance between the two roots that keeps their common levels 2 apart
+                        let common_height = min(left.height(), right.height());
+                        let distance = (0..common_height)
+                            .map(|depth| left.level(depth).1 - right.level(depth).0 + 2)
+                            .max()
+                            .unwrap();
+                        let distance = distance + distance % 2;
+
+                        offsets[left_id] = -distance / 2;
+                        offsets[right_id] = distance / 2;
+                        left.shift += offsets[left_id];
+                        right.shift += offsets[right_id];
+
+                        // the contour of the taller child is extended with the levels of the other one
+                        let (mut taller, shorter) = if left.height() >= right.height() { (left, right) } else { (right, left) };
+                        for depth in 0..shorter.height() {
+                            let (taller_left, taller_right) = taller.level(depth);
+                            let (shorter_left, shorter_right) = shorter.level(depth);
+                            taller.set_level(depth, (min(taller_left, shorter_left), max(taller_right, shorter_right)));
+                        }
+                        taller.push_root();
+                        taller
+                    }
+                };
+                contours[node_id] = Some(contour);
+            }
 
-        let new_height = max(height_left, height_right) + 1;
+            // the x of every node, starting from the root at x 0
+            let mut xs: Vec<Option<(i64, usize)>> = vec![None; self.nodes.len()];
+            for (node_id, node, depth) in self.level_order() {
+                let x = xs[node_id].map_or(0, |(x, _)| x);
+                xs[node_id] = Some((x, depth));
+                for child_id in [node.id_left, node.id_right].into_iter().flatten() {
+                    xs[child_id] = Some((x + offsets[child_id], depth + 1));
+                }
+            }
 
-        return (am_i_balanced, new_height);
+            let min_x = xs.iter().flatten().map(|(x, _)| *x).min().unwrap_or(0);
+            xs.into_iter()
+                .map(|position| position.map(|(x, depth)| ((x - min_x) as usize, depth)))
+                .collect()
+        }
     }
 
+    impl<K: Display> trees::Tree<K> {
 
+        /// # Returns
+        /// a standalone SVG image of the tree, where every node shows its key (and its id below)
+        pub fn to_svg(&self) -> String {
+            let positions = self.layout();
+            let max_x = positions.iter().flatten().map(|(x, _)| *x).max().unwrap_or(0);
+            let levels = positions.iter().flatten().map(|(_, depth)| depth + 1).max().unwrap_or(0);
+
+            // `x` is in half columns
+            let center = |node_id: usize| {
+                let (x, depth) = positions[node_id].unwrap();
+                (MARGIN + COLUMN_WIDTH / 2 + x * COLUMN_WIDTH / 2, MARGIN + depth * LEVEL_HEIGHT + LEVEL_HEIGHT / 2)
+            };
 
-}
+            let width = 2 * MARGIN + COLUMN_WIDTH + max_x * COLUMN_WIDTH / 2;
+            let height = 2 * MARGIN + levels * LEVEL_HEIGHT;
+            let mut svg = format!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"monospace\">\n",
+                width, height, width, height
+            );
 
+            // the edges are drawn first, so that the nodes are drawn on top of them
+            for (node_id, node) in self.preorder() {
+                let (x, y) = center(node_id);
+                for child_id in [node.id_left, node.id_right].into_iter().flatten() {
+                    let (child_x, child_y) = center(child_id);
+                    svg.push_str(&format!(
+                        "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\"/>\n",
+                        x, y, child_x, child_y
+                    ));
+                }
+            }
 
+            for (node_id, node) in self.preorder() {
+                let (x, y) = center(node_id);
+                svg.push_str(&format!(
+                    "  <circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"white\" stroke=\"black\"/>\n",
+                    x, y, RADIUS
+                ));
+                svg.push_str(&format!(
+                    "  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\" font-size=\"14\">{}</text>\n",
+                    x, y, escape_xml(&node.key.to_string())
+                ));
+                svg.push_str(&format!(
+                    "  <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" font-size=\"10\" fill=\"gray\">id={}</text>\n",
+                    x, y + RADIUS + 12, node_id
+                ));
+            }
 
-/// Tests for exercise 2
-#[cfg(test)]
-mod ex_2_tests {
-    use super::*;
-    use trees::*;
+            svg.push_str("</svg>\n");
+            svg
+        }
 
+        /// # Returns
+        /// a standalone HTML page with the SVG image of the tree and the given `title`
+        pub fn to_html(&self, title: &str) -> String {
+            let title = escape_xml(title);
+            format!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n</head>\n<body>\n<h1>{}</h1>\n{}</body>\n</html>\n",
+                title, title, self.to_svg()
+            )
+        }
 
-    #[test]
-    fn test_single_node() {
-        let tree = Tree::with_root(10);
+        /// Writes the SVG image of the tree (see `to_svg`) to the file at `path`
+        pub fn write_svg(&self, path: impl AsRef<Path>) -> io::Result<()> {
+            fs::write(path, self.to_svg())
+        }
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_single_node:");
+        /// Writes the HTML page of the tree (see `to_html`) to the file at `path`, with the file name as title
+        pub fn write_html(&self, path: impl AsRef<Path>) -> io::Result<()> {
+            let path = path.as_ref();
+            let title = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+            fs::write(path, self.to_html(&title))
         }
 
-        assert_eq!(tree.is_balanced(), true);
-        assert_eq!(tree.get_height(), 1);
+        /// Writes the SVG image of the tree to `<temp dir>/<name>.svg`, e.g. to look at the tree that made a check fail
+        ///
+        /// # Returns
+        /// the path of the written file
+        pub fn dump_svg(&self, name: &str) -> io::Result<PathBuf> {
+            let path = std::env::temp_dir().join(format!("{}.svg", name));
+            self.write_svg(&path)?;
+            Ok(path)
+        }
     }
+}
 
 
-    #[test]
-    fn test_two_nodes() {
-        let mut tree = Tree::with_root(10);
-        tree.add_node(0, 10, true);
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_two_nodes:");
-        }
+#[cfg(test)]
+mod svg_export_tests {
+    use super::*;
+    use trees::*;
 
-        assert_eq!(tree.is_balanced(), true);
-        assert_eq!(tree.get_height(), 2);
+    #[test]
+    fn test_layout() {
+        let tree: Tree = Tree::from_level_order("[4,2,6,1,null,5]").unwrap();
+
+        // the root is centered above its children, and a single child is half a column away from its parent
+        assert_eq!(
+            tree.layout(),
+            vec![Some((2, 0)), Some((1, 1)), Some((3, 1)), Some((0, 2)), Some((2, 2))]
+        );
+
+        // free slots have no position
+        let mut tree = tree;
+        tree.remove_subtree(1);
+        assert_eq!(tree.layout(), vec![Some((0, 0)), None, Some((1, 1)), None, Some((0, 2))]);
     }
 
-    /// this tree should be not balanced, since the left subtree has a height of 2 and the right has 0
     #[test]
-    fn test_left_subtree_unbalanced() {
-        let mut tree = Tree::with_root(10);
-        let last_id = tree.add_node(0, 10, true);
-        tree.add_node(last_id, 5, true);
+    fn test_layout_pushes_subtrees_together() {
+        // 4 (the right child of 2) and 5 (the left child of 3) would be drawn on top of each other
+        // if 2 and 3 were only one column apart
+        let tree: Tree = Tree::from_level_order("[1,2,3,null,4,5,null,6,null,null,7]").unwrap();
+        let positions = tree.layout();
 
         if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_left_subtree_unbalanced:");
+            tree.print_visualization_url("Tree inside test_layout_pushes_subtrees_together:");
         }
 
-        assert!( tree.is_balanced() == false);
-        assert_eq!(tree.get_height(), 3);
+        let x = |id: usize| positions[id].unwrap().0;
+        assert_eq!((x(1), x(2)), (0, 4));
+        assert_eq!(x(0), 2);
+        assert_eq!((x(3), x(4)), (1, 3));
+        assert_eq!((x(5), x(6)), (0, 4));
+
+        // a perfect tree is symmetric
+        let perfect: Tree = Tree::from_level_order("[1,2,3,4,5,6,7]").unwrap();
+        let xs: Vec<usize> = perfect.layout().into_iter().map(|position| position.unwrap().0).collect();
+        assert_eq!(xs, vec![3, 1, 5, 0, 2, 4, 6]);
     }
 
     #[test]
-    fn test_right_subtree_unbalanced() {
-        let mut tree = Tree::with_root(10);
-        let last_id = tree.add_node(0, 10, false);
-        tree.add_node(last_id, 5, false);
+    fn test_layout_is_tidy() {
+        let mut rng = random_trees::TreeRng::new(18);
+
+        for _ in 0..200 {
+            let size = 1 + rng.below(60);
+            let tree = Tree::random_tree(&mut rng, size, 100);
+            let positions = tree.layout();
+            let x = |id: usize| positions[id].unwrap().0 as i64;
+
+            let mut is_tidy = positions.iter().flatten().any(|(x, _)| *x == 0);
+            for (node_id, node) in tree.preorder() {
+                match (node.id_left, node.id_right) {
+                    (Some(left_id), Some(right_id)) => is_tidy &= 2 * x(node_id) == x(left_id) + x(right_id),
+                    (Some(left_id), None) => is_tidy &= x(left_id) == x(node_id) - 1,
+                    (None, Some(right_id)) => is_tidy &= x(right_id) == x(node_id) + 1,
+                    (None, None) => {}
+                }
+            }
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_right_subtree_unbalanced:");
+            // on every level, the nodes are in in-order and at least 2 apart
+            let mut last_x_of_level: Vec<Option<i64>> = vec![None; size];
+            for (node_id, _) in tree.inorder() {
+                let depth = positions[node_id].unwrap().1;
+                is_tidy &= last_x_of_level[depth].is_none_or(|last_x| last_x + 2 <= x(node_id));
+                last_x_of_level[depth] = Some(x(node_id));
+            }
+
+            test_support::assert_tree(&tree, is_tidy, "layout_is_tidy");
         }
+    }
 
-        assert!(tree.is_balanced() == false);
-        assert_eq!(tree.get_height(), 3);
+    #[test]
+    fn test_to_svg() {
+        let tree: Tree<String> = Tree::from_level_order("[<a>,b&c]").unwrap();
+        let svg = tree.to_svg();
+
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"120\" height=\"176\""));
+        assert_eq!(svg.matches("<circle").count(), 2);
+        assert_eq!(svg.matches("<line").count(), 1);
+        assert!(svg.contains(">&lt;a&gt;</text>"));
+        assert!(svg.contains(">b&amp;c</text>"));
+        // the left child is half a column to the left of the root
+        assert!(svg.contains("<circle cx=\"72\" cy=\"56\""));
+        assert!(svg.contains("<circle cx=\"48\" cy=\"120\""));
     }
 
     #[test]
-    fn test_simple_balanced_tree() {
-        let mut tree = Tree::with_root(10);
-        let _left_id = tree.add_node(0, 10, true);
-        let _right_id = tree.add_node(0, 10, false);
+    fn test_write_html() {
+        let tree: Tree = Tree::from_level_order("[10,5,20,null,7]").unwrap();
+        let path = std::env::temp_dir().join(format!("svg_export_test_{}.html", std::process::id()));
+
+        tree.write_html(&path).unwrap();
+        let html = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
 
         if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_simple_balanced_tree:");
+            println!("{}", html);
         }
 
-        assert!(tree.is_balanced() == true);
-        assert_eq!(tree.get_height(), 2);
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains(&format!("<title>svg_export_test_{}</title>", std::process::id())));
+        assert!(html.contains(&tree.to_svg()));
     }
 
-
     #[test]
-    fn test_another_balanced_tree() {
-        let mut tree = Tree::with_root(10);
-        let _left_id = tree.add_node(0, 10, true);
-        let _right_id = tree.add_node(0, 10, false);
+    fn test_write_svg() {
+        let tree = Tree::with_root(1);
+        let path = std::env::temp_dir().join(format!("svg_export_test_{}.svg", std::process::id()));
 
-        tree.add_node(_left_id, 999, true);
-        tree.add_node(_right_id, 65, false);
-
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_another_balanced_tree:");
-        }
+        tree.write_svg(&path).unwrap();
+        let svg = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
 
-        assert!(tree.is_balanced() == true);
-        assert_eq!(tree.get_height(), 3);
+        assert_eq!(svg, tree.to_svg());
     }
 
+    #[test]
+    fn test_dump_svg() {
+        let tree: Tree = Tree::from_level_order("[2,1,3]").unwrap();
+        let path = tree.dump_svg(&format!("svg_export_dump_{}", std::process::id())).unwrap();
 
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), tree.to_svg());
+        std::fs::remove_file(&path).unwrap();
+    }
 }
 
 
@@ -533,289 +1704,5365 @@ mod ex_2_tests {
 
 
 
-/// # Exercise 3
-/// Write a method to check if the binary tree is a max-heap.
-/// 
-/// A max-heap is a complete binary tree in which every node satisfies the max-heap property.
-/// 
-/// Complete Binary Tree: Every level in the binary tree (except possibly the lowest level), is completely filled, and all vertices in the last level are as far left as possible.
-/// 
-/// A node satisfies the max-heap property: if its key is greater than or equal to the keys of its children.
-
-impl trees::Tree {
+/// # Optional utility: level-order (LeetCode-style) parsing
+///
+/// Problem statements describe trees as level-order arrays with `null` holes, e.g. `[20,10,21,6,null,15]`.
+/// Only the children of non-null nodes are listed, and trailing `null`s may be omitted.
+///
+/// The nodes of a parsed tree get their ids in level order (the root has id 0).
+mod level_order {
 
-    /// # Returns
-    /// true iff the tree is a max-heap
-    pub fn is_max_heap(&self) -> bool {
-        self.rec_helper_heap(Some(0), 0, self.get_height(), 0).0
+    use crate::trees;
+    use std::collections::VecDeque;
+    use std::fmt;
+    use std::str::FromStr;
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum ParseTreeError {
+        /// the input is not enclosed in square brackets
+        MissingBrackets,
+        /// the input contains no values (e.g. `[]`), but a `Tree` always has a root
+        Empty,
+        /// the first value is `null`, so the tree would have no root
+        NullRoot,
+        /// the token at `position` (0-based, counting commas) is neither `null` nor a valid key
+        InvalidToken { position: usize, token: String },
+        /// the value at `position` does not fit in any free child slot of the previous levels
+        NoParentSlot { position: usize },
     }
 
-
-    /// # Returns
-    /// a couple, where:
-    /// - the first item is true iff the tree is a max-heap
-    /// - the second item is the max value in the tree
-    pub fn is_max_heap_with_max(&self) -> (bool, u32) {
-        let tree_height = self.get_height();
-
-        self.rec_helper_heap(Some(0), 0, tree_height, 0)
+    impl fmt::Display for ParseTreeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ParseTreeError::MissingBrackets => write!(f, "the tree must be enclosed in square brackets"),
+                ParseTreeError::Empty => write!(f, "the tree has no nodes"),
+                ParseTreeError::NullRoot => write!(f, "the root of the tree cannot be null"),
+                ParseTreeError::InvalidToken { position, token } => {
+                    write!(f, "invalid token '{}' at position {}", token, position)
+                }
+                ParseTreeError::NoParentSlot { position } => {
+                    write!(f, "the value at position {} has no parent to be attached to", position)
+                }
+            }
+        }
     }
 
+    impl std::error::Error for ParseTreeError {}
 
+    impl<K: FromStr + Clone> trees::Tree<K> {
 
+        /// Parses a tree written as a level-order array, e.g. `[20,10,21,6,null,15]`.
+        /// Whitespace around the tokens is ignored, and every other token is parsed with `K::from_str`.
+        pub fn from_level_order(input: &str) -> Result<Self, ParseTreeError> {
+            let inner = input
+                .trim()
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+                .ok_or(ParseTreeError::MissingBrackets)?;
 
-    fn rec_helper_heap(&self, curr_id_opt: Option<usize>, curr_level: u32, tree_height: u32, incomplete_nodes_count: u32) -> (bool, u32){
-        if curr_id_opt.is_none(){
-            // in a complete tree, only the two lowest level can have empty nodes
-            if (curr_level == tree_height) ||  (curr_level == tree_height-1) { 
-                return (true, curr_level); 
+            if inner.trim().is_empty() {
+                return Err(ParseTreeError::Empty);
             }
-            return (false, curr_level); 
-        }
-        let curr_id = curr_id_opt.unwrap();
 
-
-        let curr_node_opt = &self.get_node(curr_id);
-        if curr_node_opt.is_none() {
-            if (curr_level == tree_height) ||  (curr_level == tree_height-1) {
-                return (true, curr_level); 
+            let mut values = Vec::new();
+            for (position, token) in inner.split(',').enumerate() {
+                let token = token.trim();
+                if token == "null" {
+                    values.push(None);
+                    continue;
+                }
+                let key = token.parse().map_err(|_| ParseTreeError::InvalidToken {
+                    position,
+                    token: token.to_string(),
+                })?;
+                values.push(Some(key));
             }
-            return (false, curr_level); 
+
+            Self::from_level_order_values(&values)
         }
-        let curr_node = curr_node_opt.unwrap();
 
-        // complete tree property
-        // let's check if the current tree is a complete binary tree
-        let mut is_complete: bool = true;
+        /// Builds a tree from a level-order array of keys, where `None` is a missing child.
+        ///
+        /// The tree is valid by construction: every node is added with `add_node` under a node already in the tree,
+        /// so `validate()` always returns `Ok(())` on it. The errors are about the array, not about the tree.
+        pub fn from_level_order_values(values: &[Option<K>]) -> Result<Self, ParseTreeError> {
+            let root_key = match values.first() {
+                None => return Err(ParseTreeError::Empty),
+                Some(None) => return Err(ParseTreeError::NullRoot),
+                Some(Some(key)) => key.clone(),
+            };
 
-        if curr_node.id_left.is_some() && curr_node.id_right.is_none(){ // if this node has a left child but not a right child
-            let incomplete_nodes_count_new = incomplete_nodes_count + 1;
-            if incomplete_nodes_count_new > 1 { 
-                is_complete = false;
+            let mut tree = Self::with_root(root_key);
+
+            // every node in the queue still has to receive its two (possibly null) children
+            let mut parents: VecDeque<usize> = VecDeque::from([0]);
+            let mut position = 1;
+
+            while position < values.len() {
+                let parent_id = parents
+                    .pop_front()
+                    .ok_or(ParseTreeError::NoParentSlot { position })?;
+
+                for is_left in [true, false] {
+                    if position == values.len() {
+                        break;
+                    }
+                    if let Some(key) = &values[position] {
+                        parents.push_back(tree.add_node(parent_id, key.clone(), is_left));
+                    }
+                    position += 1;
+                }
             }
-        }
-
-        let (is_max_heap_left, max_left) = self.rec_helper_heap(curr_node.id_left, curr_level+1, tree_height, incomplete_nodes_count);
-        let (is_max_heap_right, max_right) = self.rec_helper_heap(curr_node.id_right, curr_level+1, tree_height, incomplete_nodes_count);
 
+            Ok(tree)
+        }
+    }
 
+    impl<K: Clone> trees::Tree<K> {
 
+        /// # Returns
+        /// the level-order array of the tree, without trailing `None`s
+        pub fn to_level_order_values(&self) -> Vec<Option<K>> {
+            let mut values = Vec::new();
+            let mut queue: VecDeque<Option<usize>> = VecDeque::from([Some(0)]);
+
+            while let Some(curr_id_opt) = queue.pop_front() {
+                match curr_id_opt.and_then(|id| self.get_node(id)) {
+                    Some(node) => {
+                        values.push(Some(node.key.clone()));
+                        queue.push_back(node.id_left);
+                        queue.push_back(node.id_right);
+                    }
+                    None => values.push(None),
+                }
+            }
 
+            while matches!(values.last(), Some(None)) {
+                values.pop();
+            }
 
+            values
+        }
+    }
 
-        // let's check the max-heap property for the current node
-        // the current node must have a value greater than its left and right subtrees
-        let curr_max = max(curr_node.key, max(max_left, max_right)   );
-        let is_max: bool = curr_node.key == curr_max;
+    impl<K: Clone + ToString> trees::Tree<K> {
 
-        // finally, we put the conditions together
-        let is_max_heap = is_max_heap_left && is_max_heap_right && is_complete && is_max;
-        return (is_max_heap, curr_max );
+        /// # Returns
+        /// the tree written as a level-order array, e.g. `[20,10,21,6,null,15]`.
+        /// It is the inverse of `from_level_order`.
+        pub fn to_level_order(&self) -> String {
+            let tokens: Vec<String> = self
+                .to_level_order_values()
+                .iter()
+                .map(|value| match value {
+                    Some(key) => key.to_string(),
+                    None => String::from("null"),
+                })
+                .collect();
+
+            format!("[{}]", tokens.join(","))
+        }
     }
 
-
 }
 
 
 
+#[cfg(test)]
+mod level_order_tests {
+    use super::*;
+    use level_order::ParseTreeError;
+    use trees::*;
+
+    #[test]
+    fn test_parse_example() {
+        let tree: Tree = Tree::from_level_order("[20,10,21,6,null,15]").unwrap();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_parse_example:");
+        }
+
+        assert_eq!(tree.nodes.len(), 5);
+        assert_eq!(tree.get_node(0).unwrap().key, 20);
+        assert_eq!(tree.get_node(1).unwrap().id_left, Some(3));
+        assert_eq!(tree.get_node(2).unwrap().id_left, Some(4));
+        assert_eq!(tree.get_node(2).unwrap().id_right, None);
+        assert_eq!(tree.sum(), 72);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let inputs = ["[1]", "[1,2]", "[1,null,2]", "[20,10,21,6,null,15]", "[5,4,8,11,null,13,4,7,2,null,null,null,1]"];
+        for input in inputs {
+            let tree: Tree = Tree::from_level_order(input).unwrap();
+            assert_eq!(tree.to_level_order(), input);
+        }
+
+        // whitespace and trailing nulls are not part of the canonical form
+        let tree: Tree = Tree::from_level_order(" [ 1 , null , 2 , null , null ] ").unwrap();
+        assert_eq!(tree.to_level_order(), "[1,null,2]");
+    }
+
+    #[test]
+    fn test_hand_built_tree() {
+        let mut tree = Tree::with_root(10);
+        let left_id = tree.add_node(0, 5, true);
+        tree.add_node(0, 15, false);
+        tree.add_node(left_id, 7, false);
+
+        assert_eq!(tree.to_level_order(), "[10,5,15,null,7]");
+        assert_eq!(tree.to_level_order_values(), vec![Some(10), Some(5), Some(15), None, Some(7)]);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(Tree::<u32>::from_level_order("1,2").err(), Some(ParseTreeError::MissingBrackets));
+        assert_eq!(Tree::<u32>::from_level_order("[]").err(), Some(ParseTreeError::Empty));
+        assert_eq!(Tree::<u32>::from_level_order("[null,1]").err(), Some(ParseTreeError::NullRoot));
+        assert_eq!(
+            Tree::<u32>::from_level_order("[1,x]").err(),
+            Some(ParseTreeError::InvalidToken { position: 1, token: String::from("x") })
+        );
+        assert_eq!(
+            Tree::<u32>::from_level_order("[1,,2]").err(),
+            Some(ParseTreeError::InvalidToken { position: 1, token: String::new() })
+        );
+        assert_eq!(
+            Tree::<u32>::from_level_order("[1,null,null,2]").err(),
+            Some(ParseTreeError::NoParentSlot { position: 3 })
+        );
+    }
+}
+
+
+
+
+
+
+
+/// # Optional utility: non-recursive traversals
+///
+/// Iterators over the nodes of the tree (rooted at id 0), implemented with explicit stacks and queues
+/// instead of recursion, so that new algorithms can be written on top of them.
+/// Every iterator yields `(id, &Node)`; the level-order one also yields the depth of the node (the root has depth 0).
+mod traversals {
+
+    use crate::trees;
+    use std::collections::VecDeque;
+
+    impl<K> trees::Tree<K> {
+
+        /// the root id, or `None` if the tree has no nodes
+        fn root_id(&self) -> Option<usize> {
+            self.get_node(0).map(|_| 0)
+        }
+
+        /// node, left subtree, right subtree
+        pub fn preorder(&self) -> Preorder<'_, K> {
+            Preorder {
+                tree: self,
+                stack: self.root_id().into_iter().collect(),
+            }
+        }
+
+        /// same as `preorder`, visiting only the subtree rooted at `node_id`
+        pub fn preorder_at(&self, node_id: usize) -> Preorder<'_, K> {
+            Preorder {
+                tree: self,
+                stack: self.get_node(node_id).map(|_| node_id).into_iter().collect(),
+            }
+        }
+
+        /// left subtree, node, right subtree
+        pub fn inorder(&self) -> Inorder<'_, K> {
+            Inorder {
+                tree: self,
+                stack: Vec::new(),
+                curr_id: self.root_id(),
+            }
+        }
+
+        /// left subtree, right subtree, node
+        pub fn postorder(&self) -> Postorder<'_, K> {
+            Postorder {
+                tree: self,
+                stack: self.root_id().map(|id| (id, false)).into_iter().collect(),
+            }
+        }
+
+        /// same as `postorder`, visiting only the subtree rooted at `node_id`
+        pub fn postorder_at(&self, node_id: usize) -> Postorder<'_, K> {
+            Postorder {
+                tree: self,
+                stack: self.get_node(node_id).map(|_| (node_id, false)).into_iter().collect(),
+            }
+        }
+
+        /// level by level, from left to right
+        pub fn level_order(&self) -> LevelOrder<'_, K> {
+            LevelOrder {
+                tree: self,
+                queue: self.root_id().map(|id| (id, 0)).into_iter().collect(),
+            }
+        }
+    }
+
+
+    pub struct Preorder<'a, K> {
+        tree: &'a trees::Tree<K>,
+        /// nodes still to be visited, the next one on top
+        stack: Vec<usize>,
+    }
+
+    impl<'a, K> Iterator for Preorder<'a, K> {
+        type Item = (usize, &'a trees::Node<K>);
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while let Some(curr_id) = self.stack.pop() {
+                if let Some(node) = self.tree.get_node(curr_id) {
+                    // the right child is pushed first, so that the left one is visited first
+                    self.stack.extend(node.id_right);
+                    self.stack.extend(node.id_left);
+                    return Some((curr_id, node));
+                }
+            }
+            None
+        }
+    }
+
+
+    pub struct Inorder<'a, K> {
+        tree: &'a trees::Tree<K>,
+        /// ancestors whose left subtree is being visited
+        stack: Vec<usize>,
+        /// root of the next subtree to be visited
+        curr_id: Option<usize>,
+    }
+
+    impl<'a, K> Iterator for Inorder<'a, K> {
+        type Item = (usize, &'a trees::Node<K>);
+
+        fn next(&mut self) -> Option<Self::Item> {
+            // go down to the leftmost node of the current subtree
+            while let Some(node) = self.curr_id.and_then(|id| self.tree.get_node(id)) {
+                self.stack.push(self.curr_id.unwrap());
+                self.curr_id = node.id_left;
+            }
+
+            let curr_id = self.stack.pop()?;
+            let node = self.tree.get_node(curr_id)?;
+            self.curr_id = node.id_right;
+            Some((curr_id, node))
+        }
+    }
+
+
+    pub struct Postorder<'a, K> {
+        tree: &'a trees::Tree<K>,
+        /// nodes still to be visited, with a flag that is true iff their children have already been pushed
+        stack: Vec<(usize, bool)>,
+    }
+
+    impl<'a, K> Iterator for Postorder<'a, K> {
+        type Item = (usize, &'a trees::Node<K>);
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while let Some((curr_id, children_pushed)) = self.stack.pop() {
+                let node = match self.tree.get_node(curr_id) {
+                    Some(node) => node,
+                    None => continue,
+                };
+
+                if children_pushed {
+                    return Some((curr_id, node));
+                }
+
+                self.stack.push((curr_id, true));
+                self.stack.extend(node.id_right.map(|id| (id, false)));
+                self.stack.extend(node.id_left.map(|id| (id, false)));
+            }
+            None
+        }
+    }
+
+
+    pub struct LevelOrder<'a, K> {
+        tree: &'a trees::Tree<K>,
+        /// nodes still to be visited, with their depth
+        queue: VecDeque<(usize, usize)>,
+    }
+
+    impl<'a, K> Iterator for LevelOrder<'a, K> {
+        type Item = (usize, &'a trees::Node<K>, usize);
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while let Some((curr_id, depth)) = self.queue.pop_front() {
+                if let Some(node) = self.tree.get_node(curr_id) {
+                    self.queue.extend(node.id_left.map(|id| (id, depth + 1)));
+                    self.queue.extend(node.id_right.map(|id| (id, depth + 1)));
+                    return Some((curr_id, node, depth));
+                }
+            }
+            None
+        }
+    }
+
+}
+
+
+
+#[cfg(test)]
+mod traversals_tests {
+    use super::*;
+    use test_support::almost_bst;
+    use trees::*;
+
+    #[test]
+    fn test_preorder() {
+        let tree = almost_bst();
+        let visit: Vec<(usize, u32)> = tree.preorder().map(|(id, node)| (id, node.key)).collect();
+        assert_eq!(visit, vec![(0, 10), (1, 5), (3, 1), (4, 8), (2, 15), (5, 7)]);
+    }
+
+    #[test]
+    fn test_inorder() {
+        let tree = almost_bst();
+        let keys: Vec<u32> = tree.inorder().map(|(_, node)| node.key).collect();
+        assert_eq!(keys, vec![1, 5, 8, 10, 15, 7]);
+    }
+
+    #[test]
+    fn test_postorder() {
+        let tree = almost_bst();
+        let ids: Vec<usize> = tree.postorder().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![3, 4, 1, 5, 2, 0]);
+    }
+
+    #[test]
+    fn test_level_order() {
+        let tree = almost_bst();
+        let visit: Vec<(u32, usize)> = tree.level_order().map(|(_, node, depth)| (node.key, depth)).collect();
+        assert_eq!(visit, vec![(10, 0), (5, 1), (15, 1), (1, 2), (8, 2), (7, 2)]);
+    }
+
+    #[test]
+    fn test_single_node() {
+        let tree = Tree::with_root(7);
+        assert_eq!(tree.preorder().count(), 1);
+        assert_eq!(tree.inorder().count(), 1);
+        assert_eq!(tree.postorder().count(), 1);
+        assert_eq!(tree.level_order().count(), 1);
+    }
+
+    /// the traversals can replace the recursive helpers, e.g. to compute the sum and the height
+    #[test]
+    fn test_algorithms_on_top_of_traversals() {
+        let tree: Tree = Tree::from_level_order("[5,4,8,11,null,13,4,7,2,null,null,null,1]").unwrap();
+
+        let sum: u32 = tree.preorder().map(|(_, node)| node.key).sum();
+        assert_eq!(sum, tree.sum());
+
+        let height = tree.level_order().map(|(_, _, depth)| depth as u32 + 1).max().unwrap();
+        assert_eq!(height, tree.get_height());
+    }
+}
+
+
+
+
+
+
+
+/// # Optional utility: structural validation
+///
+/// The fields of `Tree` and `Node` are public, so a `Tree` can be built by hand with links that do not form a tree:
+/// the recursive methods would then loop forever on a cycle, or panic on a dangling id.
+/// `validate()` checks the structure (not the keys) in `O(n)` time, without recursion.
+mod validation {
+
+    use crate::trees;
+    use std::fmt;
+
+    /// A structural problem of a `Tree`. Free slots (see `free_ids()`) are not considered nodes of the tree.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum StructuralIssue {
+        /// `nodes` is empty, so there is no root
+        MissingRoot,
+        /// the root (id 0) is a free slot
+        FreeRoot,
+        /// the free list contains an id that is out of range, or that is already in the list
+        InvalidFreeId { id: usize },
+        /// `parent_id` has a child `child_id` that is out of range or a free slot
+        DanglingChild { parent_id: usize, child_id: usize },
+        /// the root is the child of some nodes
+        RootWithParent { parent_ids: Vec<usize> },
+        /// `child_id` is the child of more than one node (or twice the child of the same node)
+        SharedChild { child_id: usize, parent_ids: Vec<usize> },
+        /// following the child links from `node_ids[0]` leads back to it, through the other nodes in order
+        Cycle { node_ids: Vec<usize> },
+        /// nodes that cannot be reached from the root
+        Unreachable { node_ids: Vec<usize> },
+    }
+
+    impl StructuralIssue {
+
+        /// # Returns
+        /// the ids of the nodes involved in the issue
+        pub fn node_ids(&self) -> Vec<usize> {
+            match self {
+                StructuralIssue::MissingRoot => vec![],
+                StructuralIssue::FreeRoot => vec![0],
+                StructuralIssue::InvalidFreeId { id } => vec![*id],
+                StructuralIssue::DanglingChild { parent_id, child_id } => vec![*parent_id, *child_id],
+                StructuralIssue::RootWithParent { parent_ids } => {
+                    std::iter::once(0).chain(parent_ids.iter().copied()).collect()
+                }
+                StructuralIssue::SharedChild { child_id, parent_ids } => {
+                    std::iter::once(*child_id).chain(parent_ids.iter().copied()).collect()
+                }
+                StructuralIssue::Cycle { node_ids } | StructuralIssue::Unreachable { node_ids } => node_ids.clone(),
+            }
+        }
+    }
+
+    impl fmt::Display for StructuralIssue {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                StructuralIssue::MissingRoot => write!(f, "the tree has no nodes"),
+                StructuralIssue::FreeRoot => write!(f, "the root (id 0) is a free slot"),
+                StructuralIssue::InvalidFreeId { id } => {
+                    write!(f, "the free slot {} is out of range or listed more than once", id)
+                }
+                StructuralIssue::DanglingChild { parent_id, child_id } => {
+                    write!(f, "node {} has a child {} that is not in the tree", parent_id, child_id)
+                }
+                StructuralIssue::RootWithParent { parent_ids } => {
+                    write!(f, "the root is the child of the nodes {:?}", parent_ids)
+                }
+                StructuralIssue::SharedChild { child_id, parent_ids } => {
+                    write!(f, "node {} has more than one parent: {:?}", child_id, parent_ids)
+                }
+                StructuralIssue::Cycle { node_ids } => write!(f, "the nodes {:?} form a cycle", node_ids),
+                StructuralIssue::Unreachable { node_ids } => {
+                    write!(f, "the nodes {:?} cannot be reached from the root", node_ids)
+                }
+            }
+        }
+    }
+
+    /// The error returned by `validate()`, with all the issues found
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct InvalidTreeError {
+        pub issues: Vec<StructuralIssue>,
+    }
+
+    impl InvalidTreeError {
+
+        /// # Returns
+        /// the ids of all the nodes involved in some issue, sorted and without duplicates
+        pub fn offending_ids(&self) -> Vec<usize> {
+            let mut ids: Vec<usize> = self.issues.iter().flat_map(StructuralIssue::node_ids).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            ids
+        }
+    }
+
+    impl fmt::Display for InvalidTreeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "the tree is not valid:")?;
+            for issue in &self.issues {
+                write!(f, "\n- {}", issue)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl std::error::Error for InvalidTreeError {}
+
+    impl<K> trees::Tree<K> {
+
+        /// Checks that the child links form a tree rooted at id 0: every child id is a node of the tree,
+        /// every node except the root has exactly one parent, and every node can be reached from the root.
+        ///
+        /// # Returns
+        /// `Ok(())` if the tree is valid, otherwise an error with every issue found
+        pub fn validate(&self) -> Result<(), InvalidTreeError> {
+            let issues = self.structural_issues();
+            if issues.is_empty() {
+                Ok(())
+            } else {
+                Err(InvalidTreeError { issues })
+            }
+        }
+
+        fn structural_issues(&self) -> Vec<StructuralIssue> {
+            if self.nodes.is_empty() {
+                return vec![StructuralIssue::MissingRoot];
+            }
+
+            let mut issues = Vec::new();
+
+            let mut is_free = vec![false; self.nodes.len()];
+            for &id in self.free_ids() {
+                if id >= self.nodes.len() || is_free[id] {
+                    issues.push(StructuralIssue::InvalidFreeId { id });
+                } else {
+                    is_free[id] = true;
+                }
+            }
+            let live_ids: Vec<usize> = (0..self.nodes.len()).filter(|&id| !is_free[id]).collect();
+
+            if is_free[0] {
+                issues.push(StructuralIssue::FreeRoot);
+            }
+
+            // the valid child links of every live node, and the parents of every node
+            let mut children: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+            let mut parents: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+            for &parent_id in &live_ids {
+                let node = &self.nodes[parent_id];
+                for child_id in [node.id_left, node.id_right].into_iter().flatten() {
+                    if child_id >= self.nodes.len() || is_free[child_id] {
+                        issues.push(StructuralIssue::DanglingChild { parent_id, child_id });
+                    } else {
+                        children[parent_id].push(child_id);
+                        parents[child_id].push(parent_id);
+                    }
+                }
+            }
+
+            for &child_id in &live_ids {
+                // the root must have no parent, any other node at most one (exactly one, if it is reachable)
+                if child_id == 0 && !parents[0].is_empty() {
+                    issues.push(StructuralIssue::RootWithParent { parent_ids: parents[0].clone() });
+                } else if parents[child_id].len() > 1 {
+                    issues.push(StructuralIssue::SharedChild { child_id, parent_ids: parents[child_id].clone() });
+                }
+            }
+
+            issues.extend(Self::cycles(&live_ids, &children));
+
+            if !is_free[0] {
+                let mut is_reached = vec![false; self.nodes.len()];
+                let mut stack = vec![0];
+                while let Some(curr_id) = stack.pop() {
+                    if !is_reached[curr_id] {
+                        is_reached[curr_id] = true;
+                        stack.extend(&children[curr_id]);
+                    }
+                }
+
+                let unreachable: Vec<usize> = live_ids.iter().copied().filter(|&id| !is_reached[id]).collect();
+                if !unreachable.is_empty() {
+                    issues.push(StructuralIssue::Unreachable { node_ids: unreachable });
+                }
+            }
+
+            issues
+        }
+
+        /// Finds the cycles of the graph of the child links with an iterative DFS:
+        /// an edge to a node that is still on the DFS path closes a cycle.
+        fn cycles(live_ids: &[usize], children: &[Vec<usize>]) -> Vec<StructuralIssue> {
+            #[derive(Clone, Copy, PartialEq)]
+            enum State {
+                NotVisited,
+                OnPath,
+                Done,
+            }
+
+            let mut state = vec![State::NotVisited; children.len()];
+            let mut cycles = Vec::new();
+
+            for &start_id in live_ids {
+                if state[start_id] != State::NotVisited {
+                    continue;
+                }
+
+                // the DFS path, with the index of the next child to follow for every node on it
+                let mut path: Vec<(usize, usize)> = vec![(start_id, 0)];
+                state[start_id] = State::OnPath;
+
+                while let Some((curr_id, next_child)) = path.last_mut() {
+                    let curr_id = *curr_id;
+                    match children[curr_id].get(*next_child) {
+                        Some(&child_id) => {
+                            *next_child += 1;
+                            match state[child_id] {
+                                State::NotVisited => {
+                                    state[child_id] = State::OnPath;
+                                    path.push((child_id, 0));
+                                }
+                                State::OnPath => {
+                                    let cycle_start = path.iter().position(|&(id, _)| id == child_id).unwrap();
+                                    let node_ids = path[cycle_start..].iter().map(|&(id, _)| id).collect();
+                                    cycles.push(StructuralIssue::Cycle { node_ids });
+                                }
+                                State::Done => {}
+                            }
+                        }
+                        None => {
+                            state[curr_id] = State::Done;
+                            path.pop();
+                        }
+                    }
+                }
+            }
+
+            cycles
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod validation_tests {
+    use super::*;
+    use test_support::perfect_tree;
+    use trees::*;
+    use validation::StructuralIssue;
+
+    #[test]
+    fn test_valid_trees() {
+        assert_eq!(perfect_tree().validate(), Ok(()));
+        assert_eq!(Tree::with_root(1).validate(), Ok(()));
+
+        // free slots are not nodes of the tree
+        let mut tree = perfect_tree();
+        tree.remove_subtree(1);
+        assert_eq!(tree.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let mut tree = Tree::with_root(1);
+        tree.nodes.clear();
+        assert_eq!(tree.validate().unwrap_err().issues, vec![StructuralIssue::MissingRoot]);
+    }
+
+    #[test]
+    fn test_dangling_child() {
+        let mut tree = perfect_tree();
+        tree.nodes[3].id_left = Some(42);
+
+        let error = tree.validate().unwrap_err();
+        assert_eq!(error.issues, vec![StructuralIssue::DanglingChild { parent_id: 3, child_id: 42 }]);
+        assert_eq!(error.offending_ids(), vec![3, 42]);
+
+        // a link to a free slot
+        let mut tree = perfect_tree();
+        tree.detach_child(1, true);
+        tree.nodes[4].id_right = Some(3);
+        assert_eq!(tree.validate().unwrap_err().issues, vec![StructuralIssue::DanglingChild { parent_id: 4, child_id: 3 }]);
+    }
+
+    #[test]
+    fn test_shared_child() {
+        let mut tree = perfect_tree();
+        tree.nodes[5].id_left = Some(4);
+
+        let error = tree.validate().unwrap_err();
+        assert_eq!(error.issues, vec![StructuralIssue::SharedChild { child_id: 4, parent_ids: vec![1, 5] }]);
+
+        // the same node as both children
+        let mut tree = perfect_tree();
+        tree.nodes[5].id_left = Some(4);
+        tree.nodes[1].id_right = None;
+        tree.nodes[5].id_right = Some(4);
+        assert_eq!(tree.validate().unwrap_err().issues, vec![StructuralIssue::SharedChild { child_id: 4, parent_ids: vec![5, 5] }]);
+    }
+
+    #[test]
+    fn test_cycle() {
+        // a node pointing back to the root
+        let mut tree = perfect_tree();
+        tree.nodes[4].id_left = Some(0);
+
+        let error = tree.validate().unwrap_err();
+
+        if PRINT_TREE {
+            println!("{}", error);
+        }
+
+        assert_eq!(
+            error.issues,
+            vec![
+                StructuralIssue::RootWithParent { parent_ids: vec![4] },
+                StructuralIssue::Cycle { node_ids: vec![0, 1, 4] },
+            ]
+        );
+        assert_eq!(error.offending_ids(), vec![0, 1, 4]);
+
+        // a self-loop
+        let mut tree = perfect_tree();
+        tree.nodes[3].id_right = Some(3);
+        assert_eq!(
+            tree.validate().unwrap_err().issues,
+            vec![
+                StructuralIssue::SharedChild { child_id: 3, parent_ids: vec![1, 3] },
+                StructuralIssue::Cycle { node_ids: vec![3] },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable() {
+        let mut tree = perfect_tree();
+        tree.nodes[0].id_right = None;
+
+        let error = tree.validate().unwrap_err();
+        assert_eq!(error.issues, vec![StructuralIssue::Unreachable { node_ids: vec![2, 5, 6] }]);
+
+        // an unreachable cycle
+        tree.nodes[5].id_left = Some(2);
+        assert_eq!(
+            tree.validate().unwrap_err().issues,
+            vec![StructuralIssue::Cycle { node_ids: vec![2, 5] }, StructuralIssue::Unreachable { node_ids: vec![2, 5, 6] }]
+        );
+    }
+
+    #[test]
+    fn test_invalid_free_ids() {
+        let nodes = vec![
+            Node { key: 1, id_left: None, id_right: None },
+            Node { key: 2, id_left: None, id_right: None },
+        ];
+        let tree: Tree = Tree::from_raw_parts(nodes, vec![1, 5, 1]);
+
+        assert_eq!(
+            tree.validate().unwrap_err().issues,
+            vec![StructuralIssue::InvalidFreeId { id: 5 }, StructuralIssue::InvalidFreeId { id: 1 }]
+        );
+    }
+}
+
+
+
+
+
+
+
+
+/// demo to show the print_visualization_url method
+fn main() {
+    println!("Hello, trees handson!");
+
+    // Create your binary tree
+    let mut tree = trees::Tree::with_root(10);
+    let _node_b = tree.add_node(0, 5, true);
+    let _node_c = tree.add_node(0, 15, false);
+    let _node_d = tree.add_node(_node_b, 3, true);
+    let _node_e = tree.add_node(_node_b, 7, false);
+
+    tree.print_visualization_url("This is a demo to show the print_visualization_url method.");
+}
+
+
+
+
+
+
+
+/// # Exercise 1
+/// 
+/// Write a method to check if the binary tree is a Binary Search Tree.
+impl<K: Ord> trees::Tree<K> {
+
+
+    /// # Returns
+    /// True iff the tree (rooted at id 0) is a binary search tree. False otherwise.
+    pub fn is_bst(&self) -> bool {
+        self.helper_is_bst(0)
+    }
+
+    /// # Returns
+    /// True iff the subtree rooted at `node_id` is a binary search tree. False otherwise.
+    pub fn is_bst_at(&self, node_id: usize) -> bool {
+        assert!(self.get_node(node_id).is_some(), "Node id is out of range or a free slot");
+        self.helper_is_bst(node_id)
+    }
+
+
+    /// Helper function for is_bst, computed bottom-up with `fold_postorder_at`
+    /// 
+    /// For every subtree, the fold computes a tuple `(is_bst, min, max)`,
+    /// where `min` is the minimum value in the subtree, and `max` is the maximum.
+    /// An empty subtree does not constrain the key of its parent, so no key is needed as a sentinel.
+    fn helper_is_bst(&self, root_id: usize) -> bool {
+        let result = self.fold_postorder_at(root_id, |node, result_left: Option<(bool, &K, &K)>, result_right: Option<(bool, &K, &K)>| {
+            let (is_left_bst, min_left, max_left) = result_left.unwrap_or((true, &node.key, &node.key));
+            let (is_right_bst, min_right, max_right) = result_right.unwrap_or((true, &node.key, &node.key));
+
+            let am_i_bst = is_left_bst && is_right_bst && max_left <= &node.key && &node.key <= min_right;
+
+            (am_i_bst, min(min_left, &node.key), max(max_right, &node.key))
+        });
+
+        result.is_none_or(|(is_bst, _, _)| is_bst)
+    }
+
+
+
+}
+
+
+
+/// # Tests for exercise 1
+#[cfg(test)]
+mod ex_1_tests {
+    use super::*;
+
+    fn build_example_bst() -> trees::Tree {
+        let mut tree = trees::Tree::with_root(20);
+
+        // first level
+        tree.add_node(0, 10, true); // id 1
+        tree.add_node(0, 21, false); // id 2
+
+        // second level
+        tree.add_node(1, 6, true); // id 3
+        tree.add_node(1, 15, false); // id 4
+
+        tree.add_node(2, 20, true); // id 5
+        tree.add_node(2, 100, false); //id 6
+
+        // third level
+        tree.add_node(3,1, true); // id 7
+        tree.add_node(3, 8, false); // id 8
+
+        tree.add_node(4, 14, true); // id 9
+        tree.add_node(4, 18, false); // id 10
+
+        tree.add_node(6, 115, false); // id 11
+
+        // fourth level
+        tree.add_node(11, 115, false); // id 12
+        tree.add_node(11, 115, true);
+
+        return tree;
+    }
+
+    #[test]
+    fn test_is_bst_1() {
+        // let's check if this tree is recognized as a bst
+        let mut ex_tree = build_example_bst();
+        assert!(ex_tree.is_bst());
+
+        // let's add a node to this tree so that it is not a bst anymore
+        ex_tree.add_node(12, 13, false);
+
+        if PRINT_TREE {
+            ex_tree.print_visualization_url("Tree inside test_is_bst_1:");
+        }
+
+        assert!(ex_tree.is_bst() == false , "this should not be a bst!");
+    }
+
+    // let's try another tree that is not a bst
+    #[test]
+    fn test_is_bst_2() {
+        let mut t2: trees::Tree = build_example_bst();
+        t2.add_node(12, 80, true);
+
+        if PRINT_TREE {
+            t2.print_visualization_url("Tree inside test_is_bst_2:");
+        }
+
+        assert!(t2.is_bst() == false , "this should not be a bst!");
+    }
+
+    // the same tree as build_example_bst, written as a level-order array
+    #[test]
+    fn test_example_bst_from_level_order() {
+        let tree: trees::Tree = trees::Tree::from_level_order(
+            "[20,10,21,6,15,20,100,1,8,14,18,null,null,null,115,null,null,null,null,null,null,null,null,115,115]"
+        ).unwrap();
+
+        assert!(tree.is_bst());
+        assert_eq!(tree.to_level_order(), build_example_bst().to_level_order());
+    }
+
+    #[test]
+    fn test_is_bst_extreme_keys() {
+        let tree: trees::Tree = trees::Tree::from_level_order("[0,null,4294967295,0,4294967295]").unwrap();
+        assert!(tree.is_bst());
+
+        let tree: trees::Tree = trees::Tree::from_level_order("[0,4294967295]").unwrap();
+        assert!(tree.is_bst() == false);
+    }
+
+    #[test]
+    fn test_is_bst_signed_keys() {
+        let tree: trees::Tree<i64> = trees::Tree::from_level_order("[-5,-10,3,-20,-7,0,8]").unwrap();
+        assert!(tree.is_bst());
+        assert_eq!(tree.sum(), -31);
+
+        let tree: trees::Tree<i64> = trees::Tree::from_level_order("[-5,-10,3,-20,-2]").unwrap();
+        assert!(tree.is_bst() == false);
+    }
+
+    #[test]
+    fn test_is_bst_string_keys() {
+        let mut tree = trees::Tree::with_root(String::from("m"));
+        let left_id = tree.add_node(0, String::from("c"), true);
+        tree.add_node(0, String::from("x"), false);
+        tree.add_node(left_id, String::from("a"), true);
+        assert!(tree.is_bst());
+
+        tree.add_node(left_id, String::from("p"), false);
+        assert!(tree.is_bst() == false);
+    }
+}
+
+
+
+
+
+
+
+
+
+
+/// # Exercise 2
+/// 
+/// Write a method to check if the binary tree is balanced.
+/// 
+/// A tree is considered balanced if, for each of its nodes, the heights of its left and right subtrees differ by at most one.
+/// 
+/// /// # Pseudocode:
+///
+/// ```
+/// #[cfg(doctest)]
+/// is_balanced(node) -> (bool, int) {
+///     if node is None {
+///         return (True, 0)
+///     }
+///
+///     let (is_balanced_left, height_left) = is_balanced(node.left);
+///     let (is_balanced_right, height_right) = is_balanced(node.right);
+///
+///     let am_i_balanced = is_balanced_left && is_balanced_right && abs(height_left - height_right) <= 1;
+///     let new_height = max(height_left, height_right) + 1;
+///
+///     return (am_i_balanced, new_height);
+/// }
+/// ```
+///
+impl<K> trees::Tree<K> {
+
+    pub fn is_balanced(&self) -> bool {
+        self.helper_is_balanced(0).0
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.helper_is_balanced(0).1
+    }
+
+    /// Same as `is_balanced`, for the subtree rooted at `node_id`
+    pub fn is_balanced_at(&self, node_id: usize) -> bool {
+        assert!(self.get_node(node_id).is_some(), "Node id is out of range or a free slot");
+        self.helper_is_balanced(node_id).0
+    }
+
+    /// Same as `get_height`, for the subtree rooted at `node_id`
+    pub fn get_height_at(&self, node_id: usize) -> u32 {
+        assert!(self.get_node(node_id).is_some(), "Node id is out of range or a free slot");
+        self.helper_is_balanced(node_id).1
+    }
+
+    /// Checks if the binary tree rooted at `root_id` is balanced.
+    ///
+    /// The pseudocode above is computed bottom-up with `fold_postorder_at`, so that no recursion is needed.
+    ///
+    /// # Returns
+    ///
+    /// A tuple `(bool, u32)` where the first element is `true` if the tree is balanced, and the second element
+    /// represents the height of the tree.
+    fn helper_is_balanced(&self, root_id: usize) -> (bool, u32) {
+        let result = self.fold_postorder_at(root_id, |_, result_left: Option<(bool, u32)>, result_right: Option<(bool, u32)>| {
+            let (is_balanced_left, height_left) = result_left.unwrap_or((true, 0));
+            let (is_balanced_right, height_right) = result_right.unwrap_or((true, 0));
+
+            let am_i_balanced = is_balanced_left && is_balanced_right && height_left.abs_diff(height_right) <= 1;
+
+            (am_i_balanced, max(height_left, height_right) + 1)
+        });
+
+        result.unwrap_or((true, 0))
+    }
+
+
+
+}
+
+
+
+/// Tests for exercise 2
+#[cfg(test)]
+mod ex_2_tests {
+    use super::*;
+    use trees::*;
+
+
+    #[test]
+    fn test_single_node() {
+        let tree = Tree::with_root(10);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_single_node:");
+        }
+
+        assert_eq!(tree.is_balanced(), true);
+        assert_eq!(tree.get_height(), 1);
+    }
+
+
+    #[test]
+    fn test_two_nodes() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 10, true);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_two_nodes:");
+        }
+
+        assert_eq!(tree.is_balanced(), true);
+        assert_eq!(tree.get_height(), 2);
+    }
+
+    /// this tree should be not balanced, since the left subtree has a height of 2 and the right has 0
+    #[test]
+    fn test_left_subtree_unbalanced() {
+        let mut tree = Tree::with_root(10);
+        let last_id = tree.add_node(0, 10, true);
+        tree.add_node(last_id, 5, true);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_left_subtree_unbalanced:");
+        }
+
+        assert!( tree.is_balanced() == false);
+        assert_eq!(tree.get_height(), 3);
+    }
+
+    #[test]
+    fn test_right_subtree_unbalanced() {
+        let mut tree = Tree::with_root(10);
+        let last_id = tree.add_node(0, 10, false);
+        tree.add_node(last_id, 5, false);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_right_subtree_unbalanced:");
+        }
+
+        assert!(tree.is_balanced() == false);
+        assert_eq!(tree.get_height(), 3);
+    }
+
+    #[test]
+    fn test_simple_balanced_tree() {
+        let mut tree = Tree::with_root(10);
+        let _left_id = tree.add_node(0, 10, true);
+        let _right_id = tree.add_node(0, 10, false);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_simple_balanced_tree:");
+        }
+
+        assert!(tree.is_balanced() == true);
+        assert_eq!(tree.get_height(), 2);
+    }
+
+
+    #[test]
+    fn test_another_balanced_tree() {
+        let mut tree = Tree::with_root(10);
+        let _left_id = tree.add_node(0, 10, true);
+        let _right_id = tree.add_node(0, 10, false);
+
+        tree.add_node(_left_id, 999, true);
+        tree.add_node(_right_id, 65, false);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_another_balanced_tree:");
+        }
+
+        assert!(tree.is_balanced() == true);
+        assert_eq!(tree.get_height(), 3);
+    }
+
+
+}
+
+
+
+
+
+
+
+/// # Exercise 3
+/// Write a method to check if the binary tree is a max-heap.
+/// 
+/// A max-heap is a complete binary tree in which every node satisfies the max-heap property.
+/// 
+/// Complete Binary Tree: Every level in the binary tree (except possibly the lowest level), is completely filled, and all vertices in the last level are as far left as possible.
+/// 
+/// A node satisfies the max-heap property: if its key is greater than or equal to the keys of its children.
+///
+/// # Completeness with the level-order index
+/// Number the positions of a complete tree level by level, as in an array-backed heap:
+/// the root has index 0, and the children of the node with index `i` have indexes `2i+1` and `2i+2`.
+/// A tree with `n` nodes is complete iff every node has an index smaller than `n`.
+///
+/// The heap order is checked with a comparator, so the same code checks both max-heaps and min-heaps.
+impl<K> trees::Tree<K> {
+
+    /// # Returns
+    /// true iff the tree is complete and, for every node, `cmp(parent_key, child_key)` is not `Ordering::Less`.
+    ///
+    /// For example, `is_heap_by(K::cmp)` checks for a max-heap.
+    pub fn is_heap_by<F>(&self, cmp: F) -> bool
+    where
+        F: Fn(&K, &K) -> Ordering,
+    {
+        self.is_heap_by_at(0, cmp)
+    }
+
+
+    /// Same as `is_heap_by`, for the subtree rooted at `node_id`
+    /// (whose root has level-order index 0)
+    pub fn is_heap_by_at<F>(&self, node_id: usize, cmp: F) -> bool
+    where
+        F: Fn(&K, &K) -> Ordering,
+    {
+        assert!(self.get_node(node_id).is_some(), "Node id is out of range or a free slot");
+        let nodes_count = self.preorder_at(node_id).count();
+        self.helper_heap_by(node_id, nodes_count, &cmp)
+    }
+
+
+    /// # Returns
+    /// true iff the tree is a complete binary tree
+    pub fn is_complete(&self) -> bool {
+        // no pair of keys violates the order
+        self.is_heap_by(|_, _| Ordering::Equal)
+    }
+
+
+    /// Helper for `is_heap_by`: visits the subtree rooted at `root_id` with an explicit stack,
+    /// so that no recursion is needed.
+    ///
+    /// # Arguments
+    /// * `root_id` - the root of the subtree to check, with level-order index 0
+    /// * `nodes_count` - the number of nodes of the subtree
+    fn helper_heap_by<F>(&self, root_id: usize, nodes_count: usize, cmp: &F) -> bool
+    where
+        F: Fn(&K, &K) -> Ordering,
+    {
+        // nodes still to be checked, with their level-order index
+        let mut stack: Vec<(usize, usize)> = vec![(root_id, 0)];
+
+        while let Some((curr_id, index)) = stack.pop() {
+            if index >= nodes_count {
+                return false;
+            }
+
+            let curr_node = self.get_node(curr_id).unwrap(); // should always be Some(node), unless the tree is not valid
+
+            let children = [(curr_node.id_left, 2 * index + 1), (curr_node.id_right, 2 * index + 2)];
+            for (child_id_opt, child_index) in children {
+                if let Some(child_id) = child_id_opt {
+                    let child_node = self.get_node(child_id).unwrap();
+
+                    // heap order between the current node and its child
+                    if cmp(&curr_node.key, &child_node.key) == Ordering::Less {
+                        return false;
+                    }
+
+                    stack.push((child_id, child_index));
+                }
+            }
+        }
+
+        return true;
+    }
+
+
+}
+
+
+impl<K: Ord> trees::Tree<K> {
+
+    /// # Returns
+    /// true iff the tree is a max-heap
+    pub fn is_max_heap(&self) -> bool {
+        self.is_max_heap_at(0)
+    }
+
+
+    /// # Returns
+    /// true iff the tree is a min-heap (every key is smaller than or equal to the keys of its children)
+    pub fn is_min_heap(&self) -> bool {
+        self.is_min_heap_at(0)
+    }
+
+
+    /// Same as `is_max_heap`, for the subtree rooted at `node_id`
+    pub fn is_max_heap_at(&self, node_id: usize) -> bool {
+        self.is_heap_by_at(node_id, K::cmp)
+    }
+
+
+    /// Same as `is_min_heap`, for the subtree rooted at `node_id`
+    pub fn is_min_heap_at(&self, node_id: usize) -> bool {
+        self.is_heap_by_at(node_id, |parent_key, child_key| child_key.cmp(parent_key))
+    }
+}
+
+
+impl<K: Ord + Clone> trees::Tree<K> {
+
+    /// # Returns
+    /// a couple, where:
+    /// - the first item is true iff the tree is a max-heap
+    /// - the second item is the max value in the tree
+    pub fn is_max_heap_with_max(&self) -> (bool, K) {
+        let max_value = self.preorder().map(|(_, node)| &node.key).max().expect("the tree has a root");
+
+        (self.is_max_heap(), max_value.clone())
+    }
+}
+
+
+
+
+
+
+
+
+
+#[cfg(test)]
+mod max_heap_tests {
+    use super::*;
+    use trees::*;
+
+    #[test]
+    fn test_single_node_max_heap() {
+        let tree = Tree::with_root(10);
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_single_node_max_heap:");
+        }
+
+        assert_eq!(is_max_heap, true);
+        assert_eq!(max_value, 10);
+    }
+
+    #[test]
+    fn test_two_nodes_max_heap() {
+        let mut tree = Tree::with_root(10);
+        tree.add_node(0, 9, true);
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_two_nodes_max_heap:");
+        }
+
+        assert_eq!(is_max_heap, true);
+        assert_eq!(max_value, 10);
+    }
+
+    #[test]
+    fn test_max_heap_with_left_subtree_unbalanced() {
+        let mut tree = Tree::with_root(10);
+        let last_id = tree.add_node(0, 10, true);
+        tree.add_node(last_id, 5, true);
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_max_heap_with_left_subtree_unbalanced:");
+        }
+
+        assert_eq!(is_max_heap, false);
+        assert_eq!(max_value, 10);
+    }
+
+    #[test]
+    fn test_max_heap_with_right_subtree_unbalanced() {
+        let mut tree = Tree::with_root(10);
+        let last_id = tree.add_node(0, 10, false);
+        tree.add_node(last_id, 5, false);
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_max_heap_with_right_subtree_unbalanced:");
+        }
+
+        assert_eq!(is_max_heap, false);
+        assert_eq!(max_value, 10);
+    }
+
+
+    #[test]
+    #[ignore = "the tree is not complete, so is_max_heap now returns false: see test_max_heap_small_not_complete"]
+    fn test_max_heap_small() {
+        let mut tree = Tree::with_root(9);
+
+        tree.add_node(0, 5, true);
+        tree.add_node(0, 9, false);
+
+        tree.add_node(1, 4, false);
+
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_max_heap_small:");
+        }
+
+        assert_eq!(is_max_heap, true);
+        assert_eq!(max_value, 9);
+    }
+
+    /// The tree of `test_max_heap_small`. The keys are in heap order, but the tree is not complete:
+    /// the node with key 5 has a right child but no left child.
+    /// The completeness is now checked with the level-order index, so the tree is not a max-heap.
+    #[test]
+    fn test_max_heap_small_not_complete() {
+        let mut tree = Tree::with_root(9);
+
+        tree.add_node(0, 5, true);
+        tree.add_node(0, 9, false);
+
+        tree.add_node(1, 4, false);
+
+        let is_heap_ordered = tree.preorder().all(|(_, node)| {
+            [node.id_left, node.id_right].into_iter().flatten().all(|child_id| tree.nodes[child_id].key <= node.key)
+        });
+        assert_eq!(is_heap_ordered, true);
+        assert_eq!(tree.is_complete(), false);
+        assert_eq!(tree.is_max_heap_with_max(), (false, 9));
+    }
+
+
+    /// generated using https://visualgo.net/en/heap
+    #[test]
+    fn a_random_heap() {
+        let mut tree = Tree::with_root(76);
+
+        tree.add_node(0, 67, true); // id 1
+        tree.add_node(0, 36, false); // id 2
+
+        tree.add_node(1, 62, true); // id 3
+        tree.add_node(1, 62, false); // id 4
+        tree.add_node(2, 32, true); // id 5
+        tree.add_node(2, 15, false); // id 6
+
+        tree.add_node(3, 5, false); // id 7
+        tree.add_node(3, 15, true); // id 8
+        tree.add_node(4, 57, true); // id 9
+
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside a_random_heap:");
+        }
+
+        assert_eq!(is_max_heap, true);
+        assert_eq!(max_value, 76);
+    }
+
+
+    /// same as a_random_heap, but with a changed value
+    #[test]
+    fn a_random_non_heap() {
+        let mut tree = Tree::with_root(76);
+
+        tree.add_node(0, 67, true); // id 1
+        tree.add_node(0, 36, false); // id 2
+
+        tree.add_node(1, 62, true); // id 3
+        tree.add_node(1, 62, false); // id 4
+        tree.add_node(2, 32, true); // id 5
+        tree.add_node(2, 15, false); // id 6
+
+        tree.add_node(3, 5, false); // id 7
+        tree.add_node(3, 64, true); // id 8
+        tree.add_node(4, 57, true); // id 9
+
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside a_random_non_heap:");
+        }
+
+        assert_eq!(is_max_heap, false);
+        assert_eq!(max_value, 76);
+    }
+
+    #[test]
+    fn another_random_non_heap() {
+        let mut tree = Tree::with_root(76);
+
+        tree.add_node(0, 67, true); // id 1
+        tree.add_node(0, 36, false); // id 2
+
+        tree.add_node(1, 62, true); // id 3
+        tree.add_node(1, 62, false); // id 4
+        tree.add_node(2, 37, true); // id 5
+        tree.add_node(2, 15, false); // id 6
+
+        tree.add_node(3, 5, false); // id 7
+        tree.add_node(3, 15, true); // id 8
+        tree.add_node(4, 57, true); // id 9
+
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside another_random_non_heap:");
+        }
+
+        assert_eq!(is_max_heap, false);
+        assert_eq!(max_value, 76);
+    }
+
+    #[test]
+    fn non_heap_final() {
+        let mut tree = Tree::with_root(76);
+
+        tree.add_node(0, 67, true); // id 1
+        tree.add_node(0, 36, false); // id 2
+
+        tree.add_node(1, 62, true); // id 3
+        tree.add_node(1, 62, false); // id 4
+        tree.add_node(2, 32, true); // id 5
+        tree.add_node(2, 15, false); // id 6
+
+        tree.add_node(3, 5, false); // id 7
+        tree.add_node(3, 15, true); // id 8
+
+        tree.add_node(5, 1, true); // id 9
+
+        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside non_heap_final:");
+        }
+
+        assert_eq!(is_max_heap, false);
+        assert_eq!(max_value, 76);
+    }
+
+
+    #[test]
+    fn test_min_heap() {
+        let tree: Tree = Tree::from_level_order("[1,3,2,7,4,5]").unwrap();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_min_heap:");
+        }
+
+        assert_eq!(tree.is_min_heap(), true);
+        assert_eq!(tree.is_max_heap(), false);
+        assert_eq!(tree.is_complete(), true);
+    }
+
+    #[test]
+    fn test_min_heap_not_complete() {
+        // heap ordered, but the last level is not filled from the left
+        let tree: Tree = Tree::from_level_order("[1,3,2,null,4]").unwrap();
+
+        assert_eq!(tree.is_complete(), false);
+        assert_eq!(tree.is_min_heap(), false);
+    }
+
+    #[test]
+    fn test_min_heap_order_violation() {
+        let tree: Tree = Tree::from_level_order("[1,3,2,7,0]").unwrap();
+
+        assert_eq!(tree.is_complete(), true);
+        assert_eq!(tree.is_min_heap(), false);
+    }
+
+    #[test]
+    fn test_equal_keys_are_both_heaps() {
+        let tree: Tree = Tree::from_level_order("[4,4,4,4]").unwrap();
+
+        assert_eq!(tree.is_min_heap(), true);
+        assert_eq!(tree.is_max_heap(), true);
+    }
+
+    #[test]
+    fn test_complete_shapes() {
+        let complete = ["[1]", "[1,2]", "[1,2,3]", "[1,2,3,4]", "[1,2,3,4,5,6,7,8]"];
+        let not_complete = ["[1,null,2]", "[1,2,3,null,4]", "[1,2,3,4,null,5]", "[1,2,null,3]"];
+
+        for input in complete {
+            let tree: Tree = Tree::from_level_order(input).unwrap();
+            assert_eq!(tree.is_complete(), true, "{input}");
+        }
+        for input in not_complete {
+            let tree: Tree = Tree::from_level_order(input).unwrap();
+            assert_eq!(tree.is_complete(), false, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_is_heap_by_custom_comparator() {
+        // ordered by the absolute value, larger first
+        let tree: Tree<i32> = Tree::from_level_order("[-9,5,-7,3,-2]").unwrap();
+
+        assert_eq!(tree.is_heap_by(|a, b| a.abs().cmp(&b.abs())), true);
+        assert_eq!(tree.is_max_heap(), false);
+
+        // string keys ordered by length, shorter first
+        let tree: Tree<String> = Tree::from_level_order("[a,bb,ccc,dd]").unwrap();
+        assert_eq!(tree.is_heap_by(|a, b| b.len().cmp(&a.len())), true);
+        assert_eq!(tree.is_min_heap(), true);
+        assert_eq!(tree.is_heap_by(|a, b| a.len().cmp(&b.len())), false);
+    }
+
+
+
+
+}
+
+
+
+
+
+
+
+/// # Stack-safe fold over the tree
+///
+/// A recursive helper recurses once per level, so a degenerate (path-shaped) tree
+/// with a million nodes overflows the stack.
+/// `fold_postorder` computes a property bottom-up instead, visiting the nodes with the explicit stack
+/// of the `postorder()` iterator and storing the result of every subtree in a vector indexed by node id.
+/// `sum`, `get_height`, `is_balanced` and `is_bst` (and their `_at` variants) are computed with it.
+mod iterative_checks {
+
+    use crate::trees;
+
+    impl<K> trees::Tree<K> {
+
+        /// Computes `f(node, left_result, right_result)` for every node of the tree (children before parents),
+        /// where the results are `None` for missing children.
+        ///
+        /// # Returns
+        /// The result computed for the root
+        pub(crate) fn fold_postorder<'a, T, F>(&'a self, f: F) -> Option<T>
+        where
+            F: FnMut(&'a trees::Node<K>, Option<T>, Option<T>) -> T,
+        {
+            self.fold_postorder_at(0, f)
+        }
+
+        /// Same as `fold_postorder`, for the subtree rooted at `root_id`
+        ///
+        /// # Returns
+        /// The result computed for `root_id`, or `None` if it is not a node of the tree
+        pub(crate) fn fold_postorder_at<'a, T, F>(&'a self, root_id: usize, mut f: F) -> Option<T>
+        where
+            F: FnMut(&'a trees::Node<K>, Option<T>, Option<T>) -> T,
+        {
+            let mut results: Vec<Option<T>> = (0..self.nodes.len()).map(|_| None).collect();
+
+            for (curr_id, node) in self.postorder_at(root_id) {
+                // every result is taken exactly once, by the parent
+                let result_left = node.id_left.and_then(|id| results.get_mut(id)?.take());
+                let result_right = node.id_right.and_then(|id| results.get_mut(id)?.take());
+                results[curr_id] = Some(f(node, result_left, result_right));
+            }
+
+            results.get_mut(root_id)?.take()
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod iterative_checks_tests {
+    use super::*;
+    use test_support::{chain, CHAIN_LEN};
+    use trees::*;
+
+    #[test]
+    fn test_chain_is_bst() {
+        let decreasing_left = chain(true, |i| CHAIN_LEN - i);
+        assert!(decreasing_left.is_bst());
+
+        let increasing_right = chain(false, |i| i);
+        assert!(increasing_right.is_bst());
+
+        let increasing_left = chain(true, |i| i);
+        assert!(increasing_left.is_bst() == false);
+    }
+
+    #[test]
+    fn test_chain_is_balanced_and_height() {
+        let tree = chain(true, |i| i);
+        assert!(tree.is_balanced() == false);
+        assert_eq!(tree.get_height(), CHAIN_LEN);
+    }
+
+    #[test]
+    fn test_chain_sum() {
+        let tree = chain(false, |_| 1);
+        assert_eq!(tree.sum(), CHAIN_LEN);
+    }
+
+    #[test]
+    fn test_chain_is_max_heap() {
+        let tree = chain(true, |i| CHAIN_LEN - i);
+        assert!(tree.is_max_heap() == false);
+    }
+
+    #[test]
+    fn test_chain_checks_at() {
+        let tree = chain(false, |_| 1);
+        let id = (CHAIN_LEN / 2) as usize;
+        let subtree_len = CHAIN_LEN - CHAIN_LEN / 2;
+
+        assert!(tree.is_bst_at(id));
+        assert!(tree.is_balanced_at(id) == false);
+        assert_eq!(tree.get_height_at(id), subtree_len);
+        assert_eq!(tree.sum_at(id), subtree_len);
+    }
+
+    /// the fold must agree with the annotation of `subtree_infos`
+    #[test]
+    fn test_same_results_as_subtree_infos() {
+        let inputs = [
+            "[10]",
+            "[10,10]",
+            "[10,null,10,null,5]",
+            "[20,10,21,6,15,20,100,1,8,14,18,null,null,null,115]",
+            "[20,10,21,6,15,20,100,1,8,14,18,null,null,null,115,null,null,null,null,null,null,null,13]",
+            "[76,67,36,62,62,32,15,15,5,57]",
+            "[76,67,36,62,62,32,15,64,5,57]",
+            "[76,67,36,62,62,37,15,15,5,57]",
+            "[76,67,36,62,62,32,15,15,5,null,null,1]",
+            "[9,5,9,4]",
+            "[9,5,9,null,4]",
+        ];
+
+        for input in inputs {
+            let tree: Tree = Tree::from_level_order(input).unwrap();
+            let info = tree.subtree_infos()[0].unwrap();
+            assert_eq!(tree.sum(), info.sum, "sum of {}", input);
+            assert_eq!(tree.get_height(), info.height, "height of {}", input);
+            assert_eq!(tree.is_bst(), info.is_bst, "is_bst of {}", input);
+            assert_eq!(tree.is_balanced(), info.is_balanced, "is_balanced of {}", input);
+        }
+    }
+}
+
+
+
+
+
+
+
+
+/// # Diagnostic reports
+///
+/// `is_bst()`, `is_balanced()` and `is_max_heap()` only tell whether a property holds.
+/// The `check_*` methods return instead every violation of the property, with the ids and the keys (or heights) in conflict.
+/// They are implemented with the non-recursive traversals, so they also work on degenerate trees.
+mod reports {
+
+    use crate::trees;
+    use std::fmt;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Violation<K> {
+        /// BST order: `node_id` is in the left (or right) subtree of `ancestor_id`,
+        /// but its key is greater (or smaller) than the key of the ancestor
+        Order { node_id: usize, node_key: K, ancestor_id: usize, ancestor_key: K, in_left_subtree: bool },
+        /// balance: the heights of the two subtrees of `node_id` differ by more than one
+        HeightDifference { node_id: usize, height_left: u32, height_right: u32 },
+        /// heap order: the key of `child_id` is greater than the key of its parent
+        HeapOrder { parent_id: usize, parent_key: K, child_id: usize, child_key: K },
+        /// completeness: visiting the tree level by level, `node_id` comes after
+        /// the missing left (or right) child of `missing_child_of`
+        Completeness { node_id: usize, missing_child_of: usize, missing_left: bool },
+    }
+
+    impl<K> Violation<K> {
+        /// # Returns
+        /// the ids of the nodes involved in the violation
+        pub fn node_ids(&self) -> Vec<usize> {
+            match self {
+                Violation::Order { node_id, ancestor_id, .. } => vec![*node_id, *ancestor_id],
+                Violation::HeightDifference { node_id, .. } => vec![*node_id],
+                Violation::HeapOrder { parent_id, child_id, .. } => vec![*parent_id, *child_id],
+                Violation::Completeness { node_id, missing_child_of, .. } => vec![*node_id, *missing_child_of],
+            }
+        }
+    }
+
+    impl<K: fmt::Display> fmt::Display for Violation<K> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Violation::Order { node_id, node_key, ancestor_id, ancestor_key, in_left_subtree } => {
+                    let (side, relation) = if *in_left_subtree { ("left", ">") } else { ("right", "<") };
+                    write!(
+                        f,
+                        "order: node {} (key {}) is in the {} subtree of node {} (key {}), but {} {} {}",
+                        node_id, node_key, side, ancestor_id, ancestor_key, node_key, relation, ancestor_key
+                    )
+                }
+                Violation::HeightDifference { node_id, height_left, height_right } => write!(
+                    f,
+                    "height difference: the subtrees of node {} have heights {} and {}",
+                    node_id, height_left, height_right
+                ),
+                Violation::HeapOrder { parent_id, parent_key, child_id, child_key } => write!(
+                    f,
+                    "heap order: node {} (key {}) is a child of node {} (key {}), but {} > {}",
+                    child_id, child_key, parent_id, parent_key, child_key, parent_key
+                ),
+                Violation::Completeness { node_id, missing_child_of, missing_left } => write!(
+                    f,
+                    "completeness: node {} comes after the missing {} child of node {}",
+                    node_id,
+                    if *missing_left { "left" } else { "right" },
+                    missing_child_of
+                ),
+            }
+        }
+    }
+
+    /// The result of a `check_*` method: the property holds iff there are no violations
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Report<K> {
+        pub violations: Vec<Violation<K>>,
+    }
+
+    impl<K> Report<K> {
+        pub fn is_valid(&self) -> bool {
+            self.violations.is_empty()
+        }
+
+        /// # Returns
+        /// the ids of all the nodes involved in some violation, sorted and without duplicates
+        pub fn offending_ids(&self) -> Vec<usize> {
+            let mut ids: Vec<usize> = self.violations.iter().flat_map(|violation| violation.node_ids()).collect();
+            ids.sort_unstable();
+            ids.dedup();
+            ids
+        }
+    }
+
+    impl<K: fmt::Display> fmt::Display for Report<K> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.is_valid() {
+                return writeln!(f, "no violations");
+            }
+            for violation in &self.violations {
+                writeln!(f, "- {}", violation)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl<K> trees::Tree<K> {
+
+        /// Reports every node whose subtrees have heights that differ by more than one
+        pub fn check_balanced(&self) -> Report<K> {
+            let mut heights: Vec<u32> = vec![0; self.nodes.len()];
+            let mut violations = Vec::new();
+
+            for (curr_id, node) in self.postorder() {
+                let height_left = node.id_left.map_or(0, |id| heights[id]);
+                let height_right = node.id_right.map_or(0, |id| heights[id]);
+
+                if height_left.abs_diff(height_right) > 1 {
+                    violations.push(Violation::HeightDifference { node_id: curr_id, height_left, height_right });
+                }
+                heights[curr_id] = height_left.max(height_right) + 1;
+            }
+
+            Report { violations }
+        }
+
+        /// # Returns
+        /// the `Completeness` violations of the tree, in level order
+        fn completeness_violations(&self) -> Vec<Violation<K>> {
+            let mut violations = Vec::new();
+            let mut first_missing_child: Option<(usize, bool)> = None;
+
+            // the children are considered in level order, as in `is_complete`
+            for (curr_id, node, _) in self.level_order() {
+                for (child_id_opt, is_left) in [(node.id_left, true), (node.id_right, false)] {
+                    match (child_id_opt, first_missing_child) {
+                        (None, None) => first_missing_child = Some((curr_id, is_left)),
+                        (Some(child_id), Some((missing_child_of, missing_left))) => {
+                            violations.push(Violation::Completeness { node_id: child_id, missing_child_of, missing_left });
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            violations
+        }
+    }
+
+    impl<K: Ord + Clone> trees::Tree<K> {
+
+        /// Reports every node whose key is out of the range allowed by its ancestors
+        /// (only the closest ancestor that bounds the key is reported)
+        pub fn check_bst(&self) -> Report<K> {
+            let mut violations = Vec::new();
+
+            // (node id, id of the lower bound, id of the upper bound)
+            let mut stack: Vec<(usize, Option<usize>, Option<usize>)> = vec![(0, None, None)];
+
+            while let Some((curr_id, lower_id, upper_id)) = stack.pop() {
+                let node = match self.get_node(curr_id) {
+                    Some(node) => node,
+                    None => continue,
+                };
+
+                if let Some(lower_id) = lower_id {
+                    let lower_key = &self.nodes[lower_id].key;
+                    if &node.key < lower_key {
+                        violations.push(Violation::Order {
+                            node_id: curr_id,
+                            node_key: node.key.clone(),
+                            ancestor_id: lower_id,
+                            ancestor_key: lower_key.clone(),
+                            in_left_subtree: false,
+                        });
+                    }
+                }
+                if let Some(upper_id) = upper_id {
+                    let upper_key = &self.nodes[upper_id].key;
+                    if &node.key > upper_key {
+                        violations.push(Violation::Order {
+                            node_id: curr_id,
+                            node_key: node.key.clone(),
+                            ancestor_id: upper_id,
+                            ancestor_key: upper_key.clone(),
+                            in_left_subtree: true,
+                        });
+                    }
+                }
+
+                stack.extend(node.id_right.map(|id| (id, Some(curr_id), upper_id)));
+                stack.extend(node.id_left.map(|id| (id, lower_id, Some(curr_id))));
+            }
+
+            violations.sort_by_key(|violation| violation.node_ids());
+            Report { violations }
+        }
+
+        /// Reports every child with a key greater than its parent's,
+        /// and every node that comes after a missing child (in level order)
+        pub fn check_max_heap(&self) -> Report<K> {
+            let mut violations = Vec::new();
+
+            for (curr_id, node, _) in self.level_order() {
+                for child_id in [node.id_left, node.id_right].into_iter().flatten() {
+                    let child = match self.get_node(child_id) {
+                        Some(child) => child,
+                        None => continue,
+                    };
+                    if child.key > node.key {
+                        violations.push(Violation::HeapOrder {
+                            parent_id: curr_id,
+                            parent_key: node.key.clone(),
+                            child_id,
+                            child_key: child.key.clone(),
+                        });
+                    }
+                }
+            }
+
+            violations.extend(self.completeness_violations());
+            Report { violations }
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod reports_tests {
+    use super::*;
+    use reports::Violation;
+    use trees::*;
+
+    #[test]
+    fn test_check_bst() {
+        let tree: Tree = Tree::from_level_order("[10,5,15,null,null,13,20]").unwrap();
+        assert!(tree.check_bst().is_valid());
+
+        let tree: Tree = Tree::from_level_order("[10,5,15,null,12,13,20]").unwrap();
+        let report = tree.check_bst();
+
+        if PRINT_TREE {
+            tree.print_visualization_url(&format!("Tree inside test_check_bst:\n{}", report));
+        }
+
+        assert_eq!(
+            report.violations,
+            vec![Violation::Order { node_id: 3, node_key: 12, ancestor_id: 0, ancestor_key: 10, in_left_subtree: true }]
+        );
+        assert_eq!(report.offending_ids(), vec![0, 3]);
+    }
+
+    #[test]
+    fn test_check_bst_right_subtree() {
+        let tree: Tree = Tree::from_level_order("[10,5,15,null,null,8]").unwrap();
+        let report = tree.check_bst();
+        assert_eq!(
+            report.violations,
+            vec![Violation::Order { node_id: 3, node_key: 8, ancestor_id: 0, ancestor_key: 10, in_left_subtree: false }]
+        );
+        assert_eq!(
+            report.violations[0].to_string(),
+            "order: node 3 (key 8) is in the right subtree of node 0 (key 10), but 8 < 10"
+        );
+    }
+
+    #[test]
+    fn test_check_balanced() {
+        let tree: Tree = Tree::from_level_order("[1,2,3,4,null,null,null,5]").unwrap();
+        let report = tree.check_balanced();
+
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::HeightDifference { node_id: 1, height_left: 2, height_right: 0 },
+                Violation::HeightDifference { node_id: 0, height_left: 3, height_right: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_max_heap() {
+        // same tree as max_heap_tests::a_random_non_heap: 64 (id 7) is a child of 62 (id 3)
+        let tree: Tree = Tree::from_level_order("[76,67,36,62,62,32,15,64,5,57]").unwrap();
+        let report = tree.check_max_heap();
+        assert_eq!(
+            report.violations,
+            vec![Violation::HeapOrder { parent_id: 3, parent_key: 62, child_id: 7, child_key: 64 }]
+        );
+
+        // same tree as max_heap_tests::non_heap_final: the node with key 1 comes after the missing children of 62 (id 4)
+        let tree: Tree = Tree::from_level_order("[76,67,36,62,62,32,15,15,5,null,null,1]").unwrap();
+        let report = tree.check_max_heap();
+        assert_eq!(
+            report.violations,
+            vec![Violation::Completeness { node_id: 9, missing_child_of: 4, missing_left: true }]
+        );
+    }
+
+    /// the reports must agree with the boolean checks
+    #[test]
+    fn test_same_results_as_checks() {
+        let inputs = [
+            "[10]",
+            "[10,10]",
+            "[10,null,10,null,5]",
+            "[20,10,21,6,15,20,100,1,8,14,18,null,null,null,115]",
+            "[20,10,21,6,15,20,100,1,8,14,18,null,null,null,115,null,null,null,null,null,null,null,13]",
+            "[76,67,36,62,62,32,15,15,5,57]",
+            "[76,67,36,62,62,37,15,15,5,57]",
+            "[9,5,9,null,4]",
+            "[9,5,9,4]",
+            "[1,2,3,4,5,6,7,null,null,null,null,8]",
+        ];
+
+        for input in inputs {
+            let tree: Tree = Tree::from_level_order(input).unwrap();
+            assert_eq!(tree.check_bst().is_valid(), tree.is_bst(), "check_bst of {}", input);
+            assert_eq!(tree.check_balanced().is_valid(), tree.is_balanced(), "check_balanced of {}", input);
+            assert_eq!(tree.check_max_heap().is_valid(), tree.is_max_heap(), "check_max_heap of {}", input);
+        }
+    }
+}
+
+
+
+
+
+
+
+
+/// # BST operations
+///
+/// Methods to maintain a binary search tree, keeping the invariant checked by `is_bst()`.
+/// Equal keys are inserted in the right subtree.
+///
+/// All the methods assume that the tree is already a BST, and take `O(h)` time, where `h` is the height of the tree.
+/// Since a `Tree` always has a root, the only node of the tree cannot be removed.
+mod bst {
+
+    use crate::trees;
+    use std::cmp::Ordering;
+
+    impl<K: Ord> trees::Tree<K> {
+
+        /// Inserts a new node with the given `key` and returns its id
+        pub fn bst_insert(&mut self, key: K) -> usize {
+            let mut curr_id = 0;
+            loop {
+                let curr_node = &self.nodes[curr_id];
+                let is_left = key < curr_node.key;
+                let child_id_opt = if is_left { curr_node.id_left } else { curr_node.id_right };
+
+                match child_id_opt {
+                    Some(child_id) => curr_id = child_id,
+                    None => return self.add_node(curr_id, key, is_left),
+                }
+            }
+        }
+
+        /// # Returns
+        /// the id of a node with the given `key`, if any
+        pub fn bst_search(&self, key: &K) -> Option<usize> {
+            let mut curr_id_opt = Some(0);
+            while let Some(curr_id) = curr_id_opt {
+                let curr_node = self.get_node(curr_id)?;
+                curr_id_opt = match key.cmp(&curr_node.key) {
+                    Ordering::Less => curr_node.id_left,
+                    Ordering::Greater => curr_node.id_right,
+                    Ordering::Equal => return Some(curr_id),
+                };
+            }
+            None
+        }
+
+        pub fn bst_contains(&self, key: &K) -> bool {
+            self.bst_search(key).is_some()
+        }
+
+        /// Removes one node with the given `key`. Its slot is freed and will be reused by the next insertion.
+        ///
+        /// # Returns
+        /// true iff a node with the given `key` was found (and removed)
+        ///
+        /// # Panics
+        /// Panics if the node to remove is the only node of the tree.
+        pub fn bst_remove(&mut self, key: &K) -> bool {
+            self.try_bst_remove(key).unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        /// Same as `bst_remove`, but returns `RootRemoval` instead of panicking.
+        /// The tree is not modified if an error is returned.
+        pub fn try_bst_remove(&mut self, key: &K) -> Result<bool, trees::TreeError> {
+            let mut curr_id = match self.bst_search(key) {
+                Some(id) => id,
+                None => return Ok(false),
+            };
+            if curr_id == 0 && self.nodes[0].id_left.is_none() && self.nodes[0].id_right.is_none() {
+                return Err(trees::TreeError::RootRemoval);
+            }
+            let mut parent = self.bst_parent(curr_id);
+
+            // with two children, the key is replaced by the one of its successor (the minimum of the right subtree),
+            // which is then removed instead: it has no left child
+            let curr_node = &self.nodes[curr_id];
+            if let (Some(_), Some(right_id)) = (curr_node.id_left, curr_node.id_right) {
+                let mut successor_id = right_id;
+                parent = Some((curr_id, false));
+                while let Some(left_id) = self.nodes[successor_id].id_left {
+                    parent = Some((successor_id, true));
+                    successor_id = left_id;
+                }
+                self.swap_keys(curr_id, successor_id);
+                curr_id = successor_id;
+            }
+
+            // now the node to remove has at most one child, which takes its place
+            let curr_node = &self.nodes[curr_id];
+            let child_id_opt = curr_node.id_left.or(curr_node.id_right);
+
+            match parent {
+                Some((parent_id, is_left)) => {
+                    self.set_child(parent_id, is_left, child_id_opt);
+                    self.release_slot(curr_id);
+                }
+                None => {
+                    // the root always stays at id 0, so the content of its child is moved there
+                    let child_id = child_id_opt.expect("the root is not the only node");
+                    self.swap_keys(0, child_id);
+                    self.nodes[0].id_left = self.nodes[child_id].id_left;
+                    self.nodes[0].id_right = self.nodes[child_id].id_right;
+                    self.release_slot(child_id);
+                }
+            }
+
+            Ok(true)
+        }
+
+        /// # Returns
+        /// the minimum key of the tree
+        pub fn min(&self) -> &K {
+            &self.nodes[self.leftmost(0)].key
+        }
+
+        /// # Returns
+        /// the maximum key of the tree
+        pub fn max(&self) -> &K {
+            let mut curr_id = 0;
+            while let Some(right_id) = self.nodes[curr_id].id_right {
+                curr_id = right_id;
+            }
+            &self.nodes[curr_id].key
+        }
+
+        /// # Returns
+        /// the smallest key strictly greater than `key`, if any
+        pub fn successor(&self, key: &K) -> Option<&K> {
+            self.bst_bound(|curr_key| curr_key > key, true)
+        }
+
+        /// # Returns
+        /// the greatest key strictly smaller than `key`, if any
+        pub fn predecessor(&self, key: &K) -> Option<&K> {
+            self.bst_bound(|curr_key| curr_key < key, false)
+        }
+
+        /// # Returns
+        /// the greatest key smaller than or equal to `key`, if any
+        pub fn floor(&self, key: &K) -> Option<&K> {
+            self.bst_bound(|curr_key| curr_key <= key, false)
+        }
+
+        /// # Returns
+        /// the smallest key greater than or equal to `key`, if any
+        pub fn ceil(&self, key: &K) -> Option<&K> {
+            self.bst_bound(|curr_key| curr_key >= key, true)
+        }
+
+        /// Finds the smallest (if `smallest` is true) or the greatest key that satisfies `is_candidate`,
+        /// assuming that `is_candidate` is monotone with respect to the order of the keys.
+        fn bst_bound(&self, is_candidate: impl Fn(&K) -> bool, smallest: bool) -> Option<&K> {
+            let mut best = None;
+            let mut curr_id_opt = Some(0);
+
+            while let Some(curr_node) = curr_id_opt.and_then(|id| self.get_node(id)) {
+                let go_left = if is_candidate(&curr_node.key) {
+                    best = Some(&curr_node.key);
+                    smallest // a better candidate can only be on the left iff we want the smallest one
+                } else {
+                    !smallest
+                };
+                curr_id_opt = if go_left { curr_node.id_left } else { curr_node.id_right };
+            }
+
+            best
+        }
+
+        /// # Returns
+        /// the parent of `target_id` and whether `target_id` is its left child (`None` for the root).
+        ///
+        /// Only the subtrees whose range of keys can contain the key of `target_id` are visited
+        /// (both children, in case of duplicated keys).
+        fn bst_parent(&self, target_id: usize) -> Option<(usize, bool)> {
+            let target_key = &self.nodes[target_id].key;
+            let mut stack = vec![0];
+
+            while let Some(curr_id) = stack.pop() {
+                let curr_node = &self.nodes[curr_id];
+                for (child_id_opt, is_left) in [(curr_node.id_left, true), (curr_node.id_right, false)] {
+                    let child_id = match child_id_opt {
+                        Some(child_id) => child_id,
+                        None => continue,
+                    };
+                    if child_id == target_id {
+                        return Some((curr_id, is_left));
+                    }
+                    let may_contain_target = if is_left { target_key <= &curr_node.key } else { target_key >= &curr_node.key };
+                    if may_contain_target {
+                        stack.push(child_id);
+                    }
+                }
+            }
+
+            None
+        }
+    }
+
+    impl<K> trees::Tree<K> {
+
+        /// # Returns
+        /// the id of the leftmost node of the subtree rooted at `id`
+        fn leftmost(&self, id: usize) -> usize {
+            let mut curr_id = id;
+            while let Some(left_id) = self.nodes[curr_id].id_left {
+                curr_id = left_id;
+            }
+            curr_id
+        }
+
+        pub(crate) fn set_child(&mut self, parent_id: usize, is_left: bool, child_id_opt: Option<usize>) {
+            if is_left {
+                self.nodes[parent_id].id_left = child_id_opt;
+            } else {
+                self.nodes[parent_id].id_right = child_id_opt;
+            }
+        }
+
+        pub(crate) fn swap_keys(&mut self, id_a: usize, id_b: usize) {
+            if id_a == id_b {
+                return;
+            }
+            let (low, high) = (id_a.min(id_b), id_a.max(id_b));
+            let (head, tail) = self.nodes.split_at_mut(high);
+            std::mem::swap(&mut head[low].key, &mut tail[0].key);
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod bst_tests {
+    use super::*;
+    use test_support::{build_bst, inorder_keys, SortedKeys, TreeRng};
+    use trees::*;
+
+    #[test]
+    fn test_insert_and_search() {
+        let tree = build_bst(&[50, 30, 70, 20, 40, 60, 80, 30]);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_insert_and_search:");
+        }
+
+        assert!(tree.is_bst());
+        assert_eq!(inorder_keys(&tree), vec![20, 30, 30, 40, 50, 60, 70, 80]);
+        assert_eq!(tree.bst_search(&70), Some(2));
+        assert!(tree.bst_contains(&40));
+        assert!(tree.bst_contains(&45) == false);
+        assert_eq!(*tree.min(), 20);
+        assert_eq!(*tree.max(), 80);
+    }
+
+    #[test]
+    fn test_successor_predecessor_floor_ceil() {
+        let tree = build_bst(&[50, 30, 70, 20, 40, 60, 80]);
+
+        assert_eq!(tree.successor(&40), Some(&50));
+        assert_eq!(tree.successor(&45), Some(&50));
+        assert_eq!(tree.successor(&80), None);
+        assert_eq!(tree.predecessor(&60), Some(&50));
+        assert_eq!(tree.predecessor(&20), None);
+
+        assert_eq!(tree.floor(&65), Some(&60));
+        assert_eq!(tree.floor(&60), Some(&60));
+        assert_eq!(tree.floor(&10), None);
+        assert_eq!(tree.ceil(&65), Some(&70));
+        assert_eq!(tree.ceil(&70), Some(&70));
+        assert_eq!(tree.ceil(&81), None);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut tree = build_bst(&[50, 30, 70, 20, 40, 60, 80, 65]);
+
+        // leaf
+        assert!(tree.bst_remove(&20));
+        // one child
+        assert!(tree.bst_remove(&60));
+        assert!(tree.bst_remove(&30));
+        // two children
+        assert!(tree.bst_remove(&70));
+        // missing key
+        assert!(tree.bst_remove(&99) == false);
+
+        assert!(tree.is_bst());
+        assert_eq!(inorder_keys(&tree), vec![40, 50, 65, 80]);
+        assert_eq!(tree.node_count(), 4);
+        assert_eq!(tree.free_ids().len(), 4);
+
+        // the freed slots are reused
+        let new_id = tree.bst_insert(45);
+        assert!(new_id < 8);
+        assert!(tree.is_bst());
+    }
+
+    #[test]
+    fn test_remove_root() {
+        let mut tree = build_bst(&[50, 30]);
+        assert!(tree.bst_remove(&50));
+        assert_eq!(inorder_keys(&tree), vec![30]);
+        assert_eq!(tree.node_count(), 1);
+
+        let mut tree = build_bst(&[50, 30, 70, 60]);
+        assert!(tree.bst_remove(&50));
+        assert!(tree.is_bst());
+        assert_eq!(tree.get_node(0).unwrap().key, 60);
+        assert_eq!(inorder_keys(&tree), vec![30, 60, 70]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The root cannot be removed")]
+    fn test_remove_only_node() {
+        let mut tree = Tree::with_root(1);
+        tree.bst_remove(&1);
+    }
+
+    #[test]
+    fn test_try_bst_remove() {
+        let mut tree = Tree::with_root(1);
+        assert_eq!(tree.try_bst_remove(&2), Ok(false));
+        assert_eq!(tree.try_bst_remove(&1), Err(TreeError::RootRemoval));
+        assert_eq!(tree.node_count(), 1);
+
+        tree.bst_insert(2);
+        assert_eq!(tree.try_bst_remove(&1), Ok(true));
+        assert_eq!(tree.to_level_order(), "[2]");
+    }
+
+    /// random insertions and removals, compared with a sorted vector
+    #[test]
+    fn test_against_sorted_vec() {
+        let mut rng = TreeRng::new(42);
+        let mut tree = Tree::with_root(500);
+        let mut expected = SortedKeys::new(&[500]);
+
+        for _ in 0..2000 {
+            let key = rng.key(1000);
+            if rng.below(3) == 0 && expected.keys.len() > 1 {
+                assert_eq!(tree.bst_remove(&key), expected.remove(&key));
+            } else {
+                tree.bst_insert(key);
+                expected.insert(key);
+            }
+
+            let keys = &expected.keys;
+            assert_eq!(tree.node_count(), keys.len());
+            assert_eq!(*tree.min(), keys[0]);
+            assert_eq!(*tree.max(), *keys.last().unwrap());
+
+            let probe = rng.key(1000);
+            let floor = keys.iter().rev().find(|k| **k <= probe);
+            let successor = keys.iter().find(|k| **k > probe);
+            assert_eq!(tree.floor(&probe), floor);
+            assert_eq!(tree.successor(&probe), successor);
+        }
+
+        assert!(tree.is_bst());
+        assert_eq!(inorder_keys(&tree), expected.keys);
+    }
+}
+
+
+
+
+
+
+
+
+/// # AVL trees
+///
+/// A self-balancing BST built on the same `nodes` arena of `trees::Tree`:
+/// after every insertion and removal, `is_bst()` and `is_balanced()` hold, so the height is `O(log n)`.
+///
+/// The height of every node is stored in a vector indexed by node id.
+/// A rotation keeps the root of the rotated subtree in the same slot (the keys are moved instead),
+/// so the parent of the subtree never has to be updated.
+mod avl {
+
+    use crate::trees;
+    use core::cmp::max;
+    use std::cmp::Ordering;
+
+    pub struct AvlTree<K = u32> {
+        tree: trees::Tree<K>,
+        /// `heights[id]` is the height of the subtree rooted at `id`
+        heights: Vec<u32>,
+    }
+
+    impl<K: Ord> AvlTree<K> {
+        pub fn with_root(key: K) -> Self {
+            Self {
+                tree: trees::Tree::with_root(key),
+                heights: vec![1],
+            }
+        }
+
+        /// the underlying tree, e.g. to run the checks or to visualize it
+        pub fn as_tree(&self) -> &trees::Tree<K> {
+            &self.tree
+        }
+
+        pub fn into_tree(self) -> trees::Tree<K> {
+            self.tree
+        }
+
+        pub fn len(&self) -> usize {
+            self.tree.node_count()
+        }
+
+        /// always false: like a `Tree`, an `AvlTree` always has a root
+        pub fn is_empty(&self) -> bool {
+            false
+        }
+
+        pub fn height(&self) -> u32 {
+            self.heights[0]
+        }
+
+        pub fn contains(&self, key: &K) -> bool {
+            self.tree.bst_contains(key)
+        }
+
+        /// Inserts a new node with the given `key` (equal keys go to the right subtree)
+        pub fn insert(&mut self, key: K) {
+            let mut path = Vec::new();
+            let mut curr_id = 0;
+
+            let new_id = loop {
+                path.push(curr_id);
+                let curr_node = &self.tree.nodes[curr_id];
+                let is_left = key < curr_node.key;
+                let child_id_opt = if is_left { curr_node.id_left } else { curr_node.id_right };
+
+                match child_id_opt {
+                    Some(child_id) => curr_id = child_id,
+                    None => break self.tree.add_node(curr_id, key, is_left),
+                }
+            };
+            // the new node can reuse a freed slot
+            if new_id == self.heights.len() {
+                self.heights.push(1);
+            } else {
+                self.heights[new_id] = 1;
+            }
+
+            self.rebalance_path(&path);
+        }
+
+        /// Removes one node with the given `key`, freeing its slot as `Tree::bst_remove` does.
+        ///
+        /// # Returns
+        /// true iff a node with the given `key` was found (and removed)
+        ///
+        /// # Panics
+        /// Panics if the node to remove is the only node of the tree.
+        pub fn remove(&mut self, key: &K) -> bool {
+            self.try_remove(key).unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        /// Same as `remove`, but returns `RootRemoval` instead of panicking.
+        /// The tree is not modified if an error is returned.
+        pub fn try_remove(&mut self, key: &K) -> Result<bool, trees::TreeError> {
+            // ancestors of the node to remove
+            let mut path = Vec::new();
+            let mut curr_id_opt = Some(0);
+
+            let mut curr_id = loop {
+                let curr_id = match curr_id_opt {
+                    Some(curr_id) => curr_id,
+                    None => return Ok(false),
+                };
+                let curr_node = &self.tree.nodes[curr_id];
+                curr_id_opt = match key.cmp(&curr_node.key) {
+                    Ordering::Less => curr_node.id_left,
+                    Ordering::Greater => curr_node.id_right,
+                    Ordering::Equal => break curr_id,
+                };
+                path.push(curr_id);
+            };
+
+            if curr_id == 0 && self.tree.nodes[0].id_left.is_none() && self.tree.nodes[0].id_right.is_none() {
+                return Err(trees::TreeError::RootRemoval);
+            }
+
+            // with two children, the node of the successor is removed instead
+            let curr_node = &self.tree.nodes[curr_id];
+            if let (Some(_), Some(right_id)) = (curr_node.id_left, curr_node.id_right) {
+                path.push(curr_id);
+                let mut successor_id = right_id;
+                while let Some(left_id) = self.tree.nodes[successor_id].id_left {
+                    path.push(successor_id);
+                    successor_id = left_id;
+                }
+                self.tree.swap_keys(curr_id, successor_id);
+                curr_id = successor_id;
+            }
+
+            let curr_node = &self.tree.nodes[curr_id];
+            let child_id_opt = curr_node.id_left.or(curr_node.id_right);
+
+            let detached_id = match path.last() {
+                Some(&parent_id) => {
+                    let is_left = self.tree.nodes[parent_id].id_left == Some(curr_id);
+                    self.tree.set_child(parent_id, is_left, child_id_opt);
+                    curr_id
+                }
+                None => {
+                    // the root has at most one child, which is a leaf: it is moved into slot 0
+                    let child_id = child_id_opt.expect("the root is not the only node");
+                    self.tree.swap_keys(0, child_id);
+                    self.tree.nodes[0].id_left = None;
+                    self.tree.nodes[0].id_right = None;
+                    self.heights[0] = 1;
+                    child_id
+                }
+            };
+
+            self.tree.release_slot(detached_id);
+            self.rebalance_path(&path);
+
+            Ok(true)
+        }
+
+        /// Updates the heights of the nodes in `path` (from the root down to some node), rotating the unbalanced ones
+        fn rebalance_path(&mut self, path: &[usize]) {
+            for &curr_id in path.iter().rev() {
+                self.update_height(curr_id);
+
+                let balance = self.balance_factor(curr_id);
+                if balance > 1 {
+                    let left_id = self.tree.nodes[curr_id].id_left.unwrap();
+                    if self.balance_factor(left_id) < 0 {
+                        self.rotate_left(left_id);
+                    }
+                    self.rotate_right(curr_id);
+                } else if balance < -1 {
+                    let right_id = self.tree.nodes[curr_id].id_right.unwrap();
+                    if self.balance_factor(right_id) > 0 {
+                        self.rotate_right(right_id);
+                    }
+                    self.rotate_left(curr_id);
+                }
+            }
+        }
+    }
+
+    impl<K> AvlTree<K> {
+
+        fn height_of(&self, id_opt: Option<usize>) -> u32 {
+            id_opt.map_or(0, |id| self.heights[id])
+        }
+
+        fn update_height(&mut self, id: usize) {
+            let node = &self.tree.nodes[id];
+            self.heights[id] = max(self.height_of(node.id_left), self.height_of(node.id_right)) + 1;
+        }
+
+        /// height of the left subtree minus height of the right subtree
+        fn balance_factor(&self, id: usize) -> i64 {
+            let node = &self.tree.nodes[id];
+            self.height_of(node.id_left) as i64 - self.height_of(node.id_right) as i64
+        }
+
+        /// ```text
+        ///       x            y
+        ///      / \          / \
+        ///     y   C  -->   A   x
+        ///    / \              / \
+        ///   A   B            B   C
+        /// ```
+        /// The root of the subtree stays in the slot `x_id`, and `x` moves into the slot of `y`.
+        fn rotate_right(&mut self, x_id: usize) {
+            let nodes = &self.tree.nodes;
+            let y_id = nodes[x_id].id_left.expect("rotate_right needs a left child");
+            let (a, b, c) = (nodes[y_id].id_left, nodes[y_id].id_right, nodes[x_id].id_right);
+
+            self.tree.swap_keys(x_id, y_id);
+            self.tree.nodes[y_id].id_left = b;
+            self.tree.nodes[y_id].id_right = c;
+            self.tree.nodes[x_id].id_left = a;
+            self.tree.nodes[x_id].id_right = Some(y_id);
+
+            self.update_height(y_id);
+            self.update_height(x_id);
+        }
+
+        /// Mirror of `rotate_right`
+        fn rotate_left(&mut self, x_id: usize) {
+            let nodes = &self.tree.nodes;
+            let y_id = nodes[x_id].id_right.expect("rotate_left needs a right child");
+            let (a, b, c) = (nodes[x_id].id_left, nodes[y_id].id_left, nodes[y_id].id_right);
+
+            self.tree.swap_keys(x_id, y_id);
+            self.tree.nodes[y_id].id_left = a;
+            self.tree.nodes[y_id].id_right = b;
+            self.tree.nodes[x_id].id_left = Some(y_id);
+            self.tree.nodes[x_id].id_right = c;
+
+            self.update_height(y_id);
+            self.update_height(x_id);
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod avl_tests {
+    use super::*;
+    use avl::AvlTree;
+    use test_support::{inorder_keys, SortedKeys, TreeRng};
+
+    fn assert_invariants(avl: &AvlTree) {
+        let tree = avl.as_tree();
+        assert!(tree.is_bst(), "not a bst: {}", tree.to_level_order());
+        assert!(tree.is_balanced(), "not balanced: {}", tree.to_level_order());
+        assert_eq!(avl.height(), tree.get_height());
+    }
+
+    #[test]
+    fn test_sorted_insertions() {
+        // without rebalancing, this would be a path
+        let mut avl = AvlTree::with_root(0);
+        for key in 1..1000 {
+            avl.insert(key);
+            assert_invariants(&avl);
+        }
+
+        assert_eq!(avl.len(), 1000);
+        assert_eq!(avl.height(), 10);
+        assert_eq!(inorder_keys(avl.as_tree()), (0..1000).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_small_rotations() {
+        // left-right case
+        let mut avl = AvlTree::with_root(30);
+        avl.insert(10);
+        avl.insert(20);
+
+        if PRINT_TREE {
+            avl.as_tree().print_visualization_url("Tree inside test_small_rotations:");
+        }
+
+        assert_eq!(avl.as_tree().to_level_order(), "[20,10,30]");
+
+        // right-left case
+        let mut avl = AvlTree::with_root(10);
+        avl.insert(30);
+        avl.insert(20);
+        assert_eq!(avl.as_tree().to_level_order(), "[20,10,30]");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut avl = AvlTree::with_root(50);
+        for key in [30, 70, 20, 40, 60, 80, 10] {
+            avl.insert(key);
+        }
+
+        assert!(avl.remove(&50));
+        assert_invariants(&avl);
+        assert!(avl.remove(&70));
+        assert_invariants(&avl);
+        assert!(avl.remove(&80));
+        assert_invariants(&avl);
+        assert!(avl.remove(&99) == false);
+
+        assert_eq!(inorder_keys(avl.as_tree()), vec![10, 20, 30, 40, 60]);
+        assert!(avl.contains(&40));
+        assert!(avl.contains(&50) == false);
+    }
+
+    #[test]
+    fn test_try_remove() {
+        let mut avl = AvlTree::with_root(1);
+        assert_eq!(avl.try_remove(&1), Err(trees::TreeError::RootRemoval));
+        assert!(avl.contains(&1));
+
+        avl.insert(2);
+        assert_eq!(avl.try_remove(&1), Ok(true));
+        assert_eq!(inorder_keys(avl.as_tree()), vec![2]);
+    }
+
+    /// random insertions and removals, compared with a sorted vector
+    #[test]
+    fn test_against_sorted_vec() {
+        let mut rng = TreeRng::new(7);
+        let mut avl = AvlTree::with_root(50);
+        let mut expected = SortedKeys::new(&[50]);
+
+        for _ in 0..3000 {
+            let key = rng.key(100);
+            if rng.below(2) == 0 && expected.keys.len() > 1 {
+                assert_eq!(avl.remove(&key), expected.remove(&key));
+            } else {
+                avl.insert(key);
+                expected.insert(key);
+            }
+
+            assert_invariants(&avl);
+            assert_eq!(inorder_keys(avl.as_tree()), expected.keys);
+        }
+    }
+}
+
+
+
+
+
+
+
+
+/// # Parent links and LCA queries
+///
+/// Nodes only store the ids of their children. `parents()` computes the parent of every node, and `Lca`
+/// preprocesses a tree with binary lifting (`up[j][id]` is the `2^j`-th ancestor of `id`) in `O(n log n)` time,
+/// to answer ancestor queries in `O(log n)`.
+///
+/// The preprocessing is a snapshot: it has to be rebuilt after the tree is modified.
+mod lca {
+
+    use crate::trees;
+
+    impl<K> trees::Tree<K> {
+
+        /// # Returns
+        /// the parent of every node, indexed by node id (`None` for the root and for free slots)
+        pub fn parents(&self) -> Vec<Option<usize>> {
+            let mut parents = vec![None; self.nodes.len()];
+            for (curr_id, node) in self.preorder() {
+                for child_id in [node.id_left, node.id_right].into_iter().flatten() {
+                    parents[child_id] = Some(curr_id);
+                }
+            }
+            parents
+        }
+    }
+
+    pub struct Lca {
+        /// `depths[id]` is the depth of `id` (the root has depth 0), `None` if `id` is not in the tree
+        depths: Vec<Option<usize>>,
+        /// `up[j][id]` is the `2^j`-th ancestor of `id`, or the root if `id` has less than `2^j` ancestors
+        up: Vec<Vec<usize>>,
+    }
+
+    impl Lca {
+        pub fn new<K>(tree: &trees::Tree<K>) -> Self {
+            let n = tree.nodes.len();
+            let mut depths = vec![None; n];
+            let mut parents = vec![0; n];
+
+            for (curr_id, node, depth) in tree.level_order() {
+                depths[curr_id] = Some(depth);
+                for child_id in [node.id_left, node.id_right].into_iter().flatten() {
+                    parents[child_id] = curr_id;
+                }
+            }
+
+            let max_depth = depths.iter().flatten().copied().max().unwrap_or(0);
+            let levels = (usize::BITS - max_depth.leading_zeros()).max(1) as usize;
+
+            let mut up = vec![parents];
+            for j in 1..levels {
+                let next: Vec<usize> = up[j - 1].iter().map(|&ancestor| up[j - 1][ancestor]).collect();
+                up.push(next);
+            }
+
+            Self { depths, up }
+        }
+
+        /// # Returns
+        /// the depth of `id` (the root has depth 0), or `None` if `id` is not a node of the tree
+        pub fn depth(&self, id: usize) -> Option<usize> {
+            *self.depths.get(id)?
+        }
+
+        /// # Returns
+        /// the `k`-th ancestor of `id` (`id` itself for `k = 0`), or `None` if `id` has less than `k` ancestors
+        pub fn kth_ancestor(&self, id: usize, k: usize) -> Option<usize> {
+            if k > self.depth(id)? {
+                return None;
+            }
+
+            let mut curr_id = id;
+            for (j, ancestors) in self.up.iter().enumerate() {
+                if (k >> j) & 1 == 1 {
+                    curr_id = ancestors[curr_id];
+                }
+            }
+            Some(curr_id)
+        }
+
+        /// # Returns
+        /// the lowest common ancestor of `a` and `b`, or `None` if one of them is not a node of the tree
+        pub fn lca(&self, a: usize, b: usize) -> Option<usize> {
+            let (depth_a, depth_b) = (self.depth(a)?, self.depth(b)?);
+
+            // bring both nodes to the same depth
+            let (mut a, mut b) = if depth_a >= depth_b {
+                (self.kth_ancestor(a, depth_a - depth_b)?, b)
+            } else {
+                (a, self.kth_ancestor(b, depth_b - depth_a)?)
+            };
+            if a == b {
+                return Some(a);
+            }
+
+            // climb as long as the ancestors are different: in the end, a and b are children of the lca
+            for ancestors in self.up.iter().rev() {
+                if ancestors[a] != ancestors[b] {
+                    a = ancestors[a];
+                    b = ancestors[b];
+                }
+            }
+            Some(self.up[0][a])
+        }
+
+        /// # Returns
+        /// the number of edges on the path between `a` and `b`, or `None` if one of them is not a node of the tree
+        pub fn distance(&self, a: usize, b: usize) -> Option<usize> {
+            let lca = self.lca(a, b)?;
+            Some(self.depth(a)? + self.depth(b)? - 2 * self.depth(lca)?)
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod lca_tests {
+    use super::*;
+    use lca::Lca;
+    use test_support::sample_tree;
+    use trees::*;
+
+    #[test]
+    fn test_parents() {
+        let tree = sample_tree();
+        assert_eq!(tree.parents(), vec![None, Some(0), Some(0), Some(1), Some(1), Some(2), Some(4)]);
+    }
+
+    #[test]
+    fn test_depth_and_kth_ancestor() {
+        let lca = Lca::new(&sample_tree());
+
+        assert_eq!(lca.depth(0), Some(0));
+        assert_eq!(lca.depth(6), Some(3));
+        assert_eq!(lca.depth(7), None);
+
+        assert_eq!(lca.kth_ancestor(6, 0), Some(6));
+        assert_eq!(lca.kth_ancestor(6, 1), Some(4));
+        assert_eq!(lca.kth_ancestor(6, 3), Some(0));
+        assert_eq!(lca.kth_ancestor(6, 4), None);
+    }
+
+    #[test]
+    fn test_lca_and_distance() {
+        let lca = Lca::new(&sample_tree());
+
+        assert_eq!(lca.lca(6, 3), Some(1));
+        assert_eq!(lca.lca(6, 5), Some(0));
+        assert_eq!(lca.lca(4, 6), Some(4));
+        assert_eq!(lca.lca(2, 2), Some(2));
+        assert_eq!(lca.lca(2, 9), None);
+
+        assert_eq!(lca.distance(6, 5), Some(5));
+        assert_eq!(lca.distance(3, 4), Some(2));
+        assert_eq!(lca.distance(0, 0), Some(0));
+    }
+
+    /// compares the lca of every pair of nodes with the naive algorithm (climbing with the parent links)
+    #[test]
+    fn test_against_naive() {
+        let tree: Tree = Tree::from_level_order("[1,2,3,4,5,6,7,8,null,null,9,10,null,null,11,null,12,13,14,null,15]").unwrap();
+        let parents = tree.parents();
+        let lca = Lca::new(&tree);
+
+        let ancestors_of = |id: usize| {
+            let mut ancestors = vec![id];
+            while let Some(parent_id) = parents[*ancestors.last().unwrap()] {
+                ancestors.push(parent_id);
+            }
+            ancestors
+        };
+
+        for a in 0..tree.nodes.len() {
+            for b in 0..tree.nodes.len() {
+                let ancestors_b = ancestors_of(b);
+                let naive = ancestors_of(a).into_iter().find(|id| ancestors_b.contains(id));
+                assert_eq!(lca.lca(a, b), naive, "lca of {} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_chain() {
+        let mut tree = Tree::with_root(0);
+        let mut last_id = 0;
+        for i in 1..100_000 {
+            last_id = tree.add_node(last_id, i, i % 2 == 0);
+        }
+        // the chain continues with the right child of 50_000 (since 50_001 is odd)
+        let branch_id = tree.add_node(50_000, 0, true);
+
+        let lca = Lca::new(&tree);
+        assert_eq!(lca.depth(last_id), Some(99_999));
+        assert_eq!(lca.kth_ancestor(last_id, 99_999), Some(0));
+        assert_eq!(lca.lca(last_id, branch_id), Some(50_000));
+        assert_eq!(lca.distance(last_id, branch_id), Some(99_999 - 50_000 + 1));
+    }
+}
+
+
+
+
+
+
+
+
+/// # Path queries
+///
+/// Maximum root-to-leaf path sum, maximum leaf-to-leaf path sum, diameter, and root-to-leaf paths with a given sum.
+/// Every query returns the ids of the nodes on the path that realizes it, in order.
+///
+/// As in `helper_is_balanced`, every helper computes its result bottom-up from the results of the two subtrees.
+/// To avoid copying paths, the helpers only record which child continues the best downward path from every node,
+/// and the paths are rebuilt at the end following these choices.
+mod paths {
+
+    use crate::trees;
+    use std::ops::Add;
+
+    /// The best downward path of a subtree, and the best path that has its highest node in the subtree
+    struct PathInfo<W> {
+        /// weight of the best path from the root of the subtree down to a leaf
+        down: W,
+        /// weight and highest node of the best path found in the subtree, if any
+        best: Option<(W, usize)>,
+    }
+
+    impl<K> trees::Tree<K> {
+
+        /// # Returns
+        /// the path that starts at `id` and follows `next_child`, until it is `None`
+        fn follow_down(&self, id: usize, next_child: &[Option<usize>]) -> Vec<usize> {
+            let mut path = vec![id];
+            while let Some(child_id) = next_child[*path.last().unwrap()] {
+                path.push(child_id);
+            }
+            path
+        }
+
+        /// # Returns
+        /// the path that goes up from the end of the downward path of the left child of `top_id`,
+        /// reaches `top_id` and goes down along the downward path of its right child
+        fn path_through(&self, top_id: usize, next_child: &[Option<usize>]) -> Vec<usize> {
+            let top_node = &self.nodes[top_id];
+
+            let mut path = match top_node.id_left {
+                Some(left_id) => self.follow_down(left_id, next_child),
+                None => Vec::new(),
+            };
+            path.reverse();
+            path.push(top_id);
+            if let Some(right_id) = top_node.id_right {
+                path.extend(self.follow_down(right_id, next_child));
+            }
+            path
+        }
+
+        /// # Returns
+        /// the number of edges of the longest path between two nodes, and the path
+        pub fn diameter(&self) -> (usize, Vec<usize>) {
+            let mut deepest_child = vec![None; self.nodes.len()];
+            let info = self.rec_helper_diameter(Some(0), &mut deepest_child);
+
+            let (length, top_id) = info.and_then(|info| info.best).unwrap_or((0, 0));
+            (length, self.path_through(top_id, &deepest_child))
+        }
+
+        /// Here `down` is the height of the subtree (in nodes),
+        /// and `best` is the longest path (in edges) with its highest node in the subtree
+        fn rec_helper_diameter(&self, curr_id_opt: Option<usize>, deepest_child: &mut Vec<Option<usize>>) -> Option<PathInfo<usize>> {
+            let curr_id = curr_id_opt?;
+            let curr_node = self.get_node(curr_id)?;
+
+            let info_left = self.rec_helper_diameter(curr_node.id_left, deepest_child);
+            let info_right = self.rec_helper_diameter(curr_node.id_right, deepest_child);
+
+            let height_left = info_left.as_ref().map_or(0, |info| info.down);
+            let height_right = info_right.as_ref().map_or(0, |info| info.down);
+
+            deepest_child[curr_id] = if height_left >= height_right { curr_node.id_left } else { curr_node.id_right };
+
+            // the longest path through the current node goes down to the deepest leaf of both subtrees
+            let through = (height_left + height_right, curr_id);
+            let best = [info_left.and_then(|info| info.best), info_right.and_then(|info| info.best), Some(through)]
+                .into_iter()
+                .flatten()
+                .max_by_key(|(length, _)| *length);
+
+            Some(PathInfo { down: height_left.max(height_right) + 1, best })
+        }
+    }
+
+    impl<K: Copy + Ord + Add<Output = K>> trees::Tree<K> {
+
+        /// # Returns
+        /// the maximum sum of the keys on a path from the root to a leaf, and the path
+        pub fn max_root_to_leaf_sum(&self) -> (K, Vec<usize>) {
+            let mut best_child = vec![None; self.nodes.len()];
+            let info = self.rec_helper_max_path_sum(Some(0), &mut best_child).expect("the tree has a root");
+
+            (info.down, self.follow_down(0, &best_child))
+        }
+
+        /// A leaf-to-leaf path goes up from a leaf to some node with two children, and then down to another leaf.
+        ///
+        /// # Returns
+        /// the maximum sum of the keys on a path between two leaves, and the path,
+        /// or `None` if the tree has less than two leaves
+        pub fn max_leaf_to_leaf_sum(&self) -> Option<(K, Vec<usize>)> {
+            let mut best_child = vec![None; self.nodes.len()];
+            let info = self.rec_helper_max_path_sum(Some(0), &mut best_child)?;
+
+            let (sum, top_id) = info.best?;
+            Some((sum, self.path_through(top_id, &best_child)))
+        }
+
+        /// Here `down` is the maximum sum of a path from the root of the subtree to a leaf,
+        /// and `best` is the maximum sum of a leaf-to-leaf path with its highest node in the subtree
+        fn rec_helper_max_path_sum(&self, curr_id_opt: Option<usize>, best_child: &mut Vec<Option<usize>>) -> Option<PathInfo<K>> {
+            let curr_id = curr_id_opt?;
+            let curr_node = self.get_node(curr_id)?;
+
+            let info_left = self.rec_helper_max_path_sum(curr_node.id_left, best_child);
+            let info_right = self.rec_helper_max_path_sum(curr_node.id_right, best_child);
+
+            let down_left = info_left.as_ref().map(|info| info.down);
+            let down_right = info_right.as_ref().map(|info| info.down);
+
+            // a downward path must end in a leaf, so it continues in one of the existing children
+            let (down, through) = match (down_left, down_right) {
+                (None, None) => (curr_node.key, None),
+                (Some(down_left), None) => {
+                    best_child[curr_id] = curr_node.id_left;
+                    (down_left + curr_node.key, None)
+                }
+                (None, Some(down_right)) => {
+                    best_child[curr_id] = curr_node.id_right;
+                    (down_right + curr_node.key, None)
+                }
+                (Some(down_left), Some(down_right)) => {
+                    best_child[curr_id] = if down_left >= down_right { curr_node.id_left } else { curr_node.id_right };
+                    let through = (down_left + curr_node.key + down_right, curr_id);
+                    (down_left.max(down_right) + curr_node.key, Some(through))
+                }
+            };
+
+            let best = [info_left.and_then(|info| info.best), info_right.and_then(|info| info.best), through]
+                .into_iter()
+                .flatten()
+                .max_by_key(|(sum, _)| *sum);
+
+            Some(PathInfo { down, best })
+        }
+    }
+
+    impl<K: Copy + PartialEq + Add<Output = K>> trees::Tree<K> {
+
+        /// # Returns
+        /// true iff the keys on some path from the root to a leaf sum to `target`
+        pub fn has_path_sum(&self, target: K) -> bool {
+            self.root_to_leaf_path_with_sum(target).is_some()
+        }
+
+        /// # Returns
+        /// the leftmost path from the root to a leaf whose keys sum to `target`, if any
+        pub fn root_to_leaf_path_with_sum(&self, target: K) -> Option<Vec<usize>> {
+            let root_key = self.get_node(0)?.key;
+            let mut path = self.rec_helper_path_sum(0, root_key, target)?;
+            path.reverse();
+            Some(path)
+        }
+
+        /// # Arguments
+        /// * `sum` - the sum of the keys from the root to `curr_id` (included)
+        ///
+        /// # Returns
+        /// the path from a leaf up to `curr_id`, if the keys from the root to the leaf sum to `target`
+        fn rec_helper_path_sum(&self, curr_id: usize, sum: K, target: K) -> Option<Vec<usize>> {
+            let curr_node = self.get_node(curr_id)?;
+
+            if curr_node.id_left.is_none() && curr_node.id_right.is_none() {
+                return if sum == target { Some(vec![curr_id]) } else { None };
+            }
+
+            let mut path = [curr_node.id_left, curr_node.id_right]
+                .into_iter()
+                .flatten()
+                .find_map(|child_id| {
+                    let child_key = self.get_node(child_id)?.key;
+                    self.rec_helper_path_sum(child_id, sum + child_key, target)
+                })?;
+            path.push(curr_id);
+            Some(path)
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod paths_tests {
+    use super::*;
+    use test_support::signed_tree;
+    use trees::*;
+
+    fn keys_on(tree: &Tree<i32>, path: &[usize]) -> Vec<i32> {
+        path.iter().map(|id| tree.get_node(*id).unwrap().key).collect()
+    }
+
+    #[test]
+    fn test_max_root_to_leaf_sum() {
+        let tree = signed_tree();
+        let (sum, path) = tree.max_root_to_leaf_sum();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_max_root_to_leaf_sum:");
+        }
+
+        assert_eq!(sum, 24);
+        assert_eq!(path, vec![0, 1, 4, 6]);
+    }
+
+    #[test]
+    fn test_max_leaf_to_leaf_sum() {
+        let tree = signed_tree();
+        let (sum, path) = tree.max_leaf_to_leaf_sum().unwrap();
+
+        // 20 -> -4 -> -2 -> 10 -> 7 -> -1
+        assert_eq!(sum, 30);
+        assert_eq!(keys_on(&tree, &path), vec![20, -4, -2, 10, 7, -1]);
+
+        // a path has only one leaf
+        let chain: Tree<i32> = Tree::from_level_order("[1,2,null,3]").unwrap();
+        assert_eq!(chain.max_leaf_to_leaf_sum(), None);
+    }
+
+    #[test]
+    fn test_max_leaf_to_leaf_sum_in_subtree() {
+        //          -100
+        //         /    \
+        //        5      1
+        //       / \
+        //      4   6
+        let tree: Tree<i32> = Tree::from_level_order("[-100,5,1,4,6]").unwrap();
+        let (sum, path) = tree.max_leaf_to_leaf_sum().unwrap();
+        assert_eq!(sum, 15);
+        assert_eq!(keys_on(&tree, &path), vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn test_diameter() {
+        let tree = signed_tree();
+        let (length, path) = tree.diameter();
+        assert_eq!(length, 5);
+        assert_eq!(path, vec![6, 4, 1, 0, 2, 5]);
+
+        let tree = Tree::with_root(1);
+        assert_eq!(tree.diameter(), (0, vec![0]));
+
+        // the longest path does not go through the root
+        let tree: Tree = Tree::from_level_order("[1,2,null,3,4,5,null,null,6,7,null,null,8]").unwrap();
+        let (length, path) = tree.diameter();
+        assert_eq!(length, 6);
+        assert_eq!(path.len(), 7);
+        assert!(path.contains(&0) == false);
+    }
+
+    #[test]
+    fn test_path_sum() {
+        let tree = signed_tree();
+
+        // both 10 -2 8 and 10 7 -1 sum to 16: the leftmost path is returned
+        assert!(tree.has_path_sum(16));
+        assert_eq!(tree.root_to_leaf_path_with_sum(16), Some(vec![0, 1, 3]));
+        assert_eq!(tree.root_to_leaf_path_with_sum(24), Some(vec![0, 1, 4, 6]));
+
+        // 10 + -2 is not a root-to-leaf path
+        assert!(tree.has_path_sum(8) == false);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// # Array-backed binary heaps
+///
+/// Conversions between a complete `Tree` and the array representation of a binary heap,
+/// where the node with index `i` in level order has its children at indexes `2i+1` and `2i+2`.
+///
+/// The heap operations are done directly on the tree form, moving keys between the nodes (sift up and sift down)
+/// so the shape of the tree stays complete.
+mod heap_array {
+
+    use crate::trees;
+
+    impl<K: Clone> trees::Tree<K> {
+
+        /// Builds the complete tree whose level order is `keys`. The node ids are the indexes in `keys`.
+        ///
+        /// The tree is valid (and complete) by construction: the node with index `i` is added with `add_node`
+        /// under its parent `(i - 1) / 2`, which is already in the tree, so `validate()` always returns `Ok(())` on it.
+        ///
+        /// # Panics
+        /// Panics if `keys` is empty (a `Tree` always has a root).
+        pub fn from_heap_array(keys: &[K]) -> Self {
+            let (root_key, other_keys) = keys.split_first().expect("The heap array must not be empty");
+
+            let mut tree = Self::with_root(root_key.clone());
+            for (offset, key) in other_keys.iter().enumerate() {
+                let index = offset + 1;
+                tree.add_node((index - 1) / 2, key.clone(), index % 2 == 1);
+            }
+
+            tree
+        }
+
+        /// # Returns
+        /// the keys in level order, which is the array representation of the tree, or `None` if the tree is not complete
+        pub fn to_heap_array(&self) -> Option<Vec<K>> {
+            if !self.is_complete() {
+                return None;
+            }
+            Some(self.level_order().map(|(_, node, _)| node.key.clone()).collect())
+        }
+    }
+
+    impl<K> trees::Tree<K> {
+
+        /// # Returns
+        /// the node ids in level order (`ids[i]` is the node with heap index `i`), or an error if the tree is not complete
+        fn heap_ids(&self) -> Result<Vec<usize>, trees::TreeError> {
+            if !self.is_complete() {
+                return Err(trees::TreeError::NotComplete);
+            }
+            Ok(self.level_order().map(|(id, _, _)| id).collect())
+        }
+    }
+
+    impl<K: Ord> trees::Tree<K> {
+
+        /// Rearranges the keys so that every key is greater than or equal to the keys of its children,
+        /// sifting down the nodes bottom-up in `O(n)` time. The shape of the tree does not change,
+        /// so `is_max_heap()` is true afterwards iff the tree is complete.
+        pub fn heapify(&mut self) {
+            let ids: Vec<usize> = self.level_order().map(|(id, _, _)| id).collect();
+            for id in ids.into_iter().rev() {
+                self.sift_down(id);
+            }
+        }
+
+        /// Adds `key` as the last node of the heap and sifts it up.
+        ///
+        /// # Panics
+        /// Panics if the tree is not complete.
+        pub fn heap_push(&mut self, key: K) {
+            self.try_heap_push(key).unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        /// Same as `heap_push`, but returns an error instead of panicking.
+        /// The tree is not modified if an error is returned.
+        pub fn try_heap_push(&mut self, key: K) -> Result<(), trees::TreeError> {
+            let mut ids = self.heap_ids()?;
+            let mut index = ids.len();
+            let parent_id = ids[(index - 1) / 2];
+            ids.push(self.add_node(parent_id, key, index % 2 == 1));
+
+            while index > 0 {
+                let parent_index = (index - 1) / 2;
+                if self.nodes[ids[parent_index]].key >= self.nodes[ids[index]].key {
+                    break;
+                }
+                self.swap_keys(ids[parent_index], ids[index]);
+                index = parent_index;
+            }
+            Ok(())
+        }
+
+        /// Moves the key of the node `id` down, swapping it with its greatest child, until it is not smaller than its children
+        fn sift_down(&mut self, id: usize) {
+            let mut curr_id = id;
+            loop {
+                let curr_node = &self.nodes[curr_id];
+                let greatest_child = [curr_node.id_left, curr_node.id_right]
+                    .into_iter()
+                    .flatten()
+                    .max_by(|a, b| self.nodes[*a].key.cmp(&self.nodes[*b].key));
+
+                match greatest_child {
+                    Some(child_id) if self.nodes[child_id].key > self.nodes[curr_id].key => {
+                        self.swap_keys(curr_id, child_id);
+                        curr_id = child_id;
+                    }
+                    _ => return,
+                }
+            }
+        }
+    }
+
+    impl<K: Ord + Clone> trees::Tree<K> {
+
+        /// Removes the greatest key (the one in the root), moving the key of the last node to the root and sifting it down.
+        /// The slot of the last node is freed.
+        ///
+        /// # Returns
+        /// the removed key
+        ///
+        /// # Panics
+        /// Panics if the tree is not complete or it has only one node (a `Tree` always has a root).
+        pub fn heap_pop(&mut self) -> K {
+            self.try_heap_pop().unwrap_or_else(|error| panic!("{}", error))
+        }
+
+        /// Same as `heap_pop`, but returns an error instead of panicking:
+        /// `NotComplete` if the tree is not complete, `RootRemoval` if the root is its only node.
+        /// The tree is not modified if an error is returned.
+        pub fn try_heap_pop(&mut self) -> Result<K, trees::TreeError> {
+            let ids = self.heap_ids()?;
+            if ids.len() == 1 {
+                return Err(trees::TreeError::RootRemoval);
+            }
+
+            let last_index = ids.len() - 1;
+            let last_id = ids[last_index];
+            self.swap_keys(0, last_id);
+            let max_key = self.nodes[last_id].key.clone();
+            self.detach_child(ids[(last_index - 1) / 2], last_index % 2 == 1);
+
+            self.sift_down(0);
+            Ok(max_key)
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod heap_array_tests {
+    use super::*;
+    use trees::*;
+
+    #[test]
+    fn test_from_heap_array() {
+        let tree = Tree::from_heap_array(&[9, 7, 8, 3, 5, 6]);
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_from_heap_array:");
+        }
+
+        assert!(tree.is_max_heap());
+        assert_eq!(tree.to_level_order(), "[9,7,8,3,5,6]");
+        assert_eq!(tree.get_node(5).unwrap().key, 6);
+
+        let tree = Tree::from_heap_array(&[1]);
+        assert_eq!(tree.to_heap_array(), Some(vec![1]));
+    }
+
+    #[test]
+    fn test_to_heap_array() {
+        let keys = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let tree = Tree::from_heap_array(&keys);
+        assert_eq!(tree.to_heap_array(), Some(keys));
+
+        let tree: Tree = Tree::from_level_order("[1,2,3,null,4]").unwrap();
+        assert_eq!(tree.to_heap_array(), None);
+    }
+
+    #[test]
+    fn test_heapify() {
+        let mut tree = Tree::from_heap_array(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+        assert!(tree.is_max_heap() == false);
+
+        tree.heapify();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_heapify:");
+        }
+
+        assert!(tree.is_max_heap());
+        assert_eq!(tree.get_node(0).unwrap().key, 10);
+
+        let mut keys = tree.to_heap_array().unwrap();
+        keys.sort();
+        assert_eq!(keys, (1..=10).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_heapify_incomplete_tree() {
+        // the keys are heap ordered, but the tree is still not complete
+        let mut tree: Tree = Tree::from_level_order("[1,2,3,null,4]").unwrap();
+        tree.heapify();
+
+        assert!(tree.is_heap_by(|_, _| std::cmp::Ordering::Equal) == false);
+        assert!(tree.check_max_heap().violations.iter().all(|violation| matches!(violation, reports::Violation::Completeness { .. })));
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut tree = Tree::with_root(5);
+        for key in [3, 8, 1, 9, 7, 2, 8] {
+            tree.heap_push(key);
+            assert!(tree.is_max_heap());
+        }
+
+        let mut popped = Vec::new();
+        while tree.node_count() > 1 {
+            popped.push(tree.heap_pop());
+            assert!(tree.is_max_heap());
+        }
+        popped.push(tree.get_node(0).unwrap().key);
+
+        assert_eq!(popped, vec![9, 8, 8, 7, 5, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_push_reuses_free_slots() {
+        let mut tree = Tree::from_heap_array(&[9, 7, 8]);
+        assert_eq!(tree.heap_pop(), 9);
+        assert_eq!(tree.free_ids(), &[2]);
+
+        tree.heap_push(10);
+        assert_eq!(tree.free_ids(), &[] as &[usize]);
+        assert_eq!(tree.to_heap_array(), Some(vec![10, 7, 8]));
+    }
+
+    #[test]
+    #[should_panic(expected = "The root cannot be removed")]
+    fn test_pop_only_node() {
+        let mut tree = Tree::with_root(1);
+        tree.heap_pop();
+    }
+
+    #[test]
+    fn test_try_push_and_pop() {
+        let mut tree = Tree::from_heap_array(&[9, 7, 8]);
+        assert_eq!(tree.try_heap_pop(), Ok(9));
+        assert_eq!(tree.try_heap_push(10), Ok(()));
+        assert_eq!(tree.to_heap_array(), Some(vec![10, 7, 8]));
+
+        let mut only_root = Tree::with_root(1);
+        assert_eq!(only_root.try_heap_pop(), Err(TreeError::RootRemoval));
+        assert_eq!(only_root.node_count(), 1);
+
+        // 9 has only a right child
+        let mut not_complete: Tree = Tree::from_level_order("[9,null,7]").unwrap();
+        assert_eq!(not_complete.try_heap_pop(), Err(TreeError::NotComplete));
+        assert_eq!(not_complete.try_heap_push(1), Err(TreeError::NotComplete));
+        assert_eq!(not_complete.to_level_order(), "[9,null,7]");
+    }
+}
+
+
+
+
+
+
+
+
+
+/// # Subtree annotations
+///
+/// A single bottom-up pass over the tree (using the `postorder()` iterator, so it does not recurse)
+/// computes a summary of every subtree, so that queries like "the largest BST subtree" take `O(n)` time
+/// instead of running a check from every node.
+mod subtrees {
+
+    use crate::trees;
+    use core::cmp::max;
+    use core::cmp::min;
+    use std::ops::Add;
+
+    /// Summary of the subtree rooted at a node
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct SubtreeInfo<K> {
+        /// number of nodes
+        pub size: usize,
+        pub height: u32,
+        /// sum of the keys
+        pub sum: K,
+        pub min: K,
+        pub max: K,
+        pub is_bst: bool,
+        pub is_balanced: bool,
+    }
+
+    impl<K: Copy + Ord + Default + Add<Output = K>> trees::Tree<K> {
+
+        /// # Returns
+        /// the summary of the subtree rooted at every node: `infos[id]` is `None` iff `id` is a free slot (not in the tree)
+        pub fn subtree_infos(&self) -> Vec<Option<SubtreeInfo<K>>> {
+            let mut infos: Vec<Option<SubtreeInfo<K>>> = vec![None; self.nodes.len()];
+
+            for (curr_id, node) in self.postorder() {
+                let info_left = node.id_left.and_then(|id| infos[id]);
+                let info_right = node.id_right.and_then(|id| infos[id]);
+
+                let height_left = info_left.map_or(0, |info| info.height);
+                let height_right = info_right.map_or(0, |info| info.height);
+
+                let mut info = SubtreeInfo {
+                    size: 1,
+                    height: max(height_left, height_right) + 1,
+                    sum: node.key,
+                    min: node.key,
+                    max: node.key,
+                    is_bst: true,
+                    is_balanced: height_left.abs_diff(height_right) <= 1,
+                };
+
+                if let Some(left) = info_left {
+                    info.size += left.size;
+                    info.sum = info.sum + left.sum;
+                    info.min = min(info.min, left.min);
+                    info.max = max(info.max, left.max);
+                    info.is_bst &= left.is_bst && left.max <= node.key;
+                    info.is_balanced &= left.is_balanced;
+                }
+                if let Some(right) = info_right {
+                    info.size += right.size;
+                    info.sum = info.sum + right.sum;
+                    info.min = min(info.min, right.min);
+                    info.max = max(info.max, right.max);
+                    info.is_bst &= right.is_bst && node.key <= right.min;
+                    info.is_balanced &= right.is_balanced;
+                }
+
+                infos[curr_id] = Some(info);
+            }
+
+            infos
+        }
+
+        /// # Returns
+        /// a couple `(id, size)`, where `id` is the root of the subtree with the most nodes that is a BST, and `size` is its number of nodes.
+        /// Ties go to the node with the smallest id.
+        pub fn largest_bst_subtree(&self) -> (usize, usize) {
+            self.largest_subtree_where(|info| info.is_bst)
+        }
+
+        /// Same as `largest_bst_subtree`, for balanced subtrees
+        pub fn largest_balanced_subtree(&self) -> (usize, usize) {
+            self.largest_subtree_where(|info| info.is_balanced)
+        }
+
+        /// # Returns
+        /// the root and the size of the largest subtree whose summary satisfies `predicate` (every leaf satisfies it)
+        fn largest_subtree_where<F>(&self, predicate: F) -> (usize, usize)
+        where
+            F: Fn(&SubtreeInfo<K>) -> bool,
+        {
+            let mut best = (0, 0);
+            for (id, info_opt) in self.subtree_infos().into_iter().enumerate() {
+                if let Some(info) = info_opt {
+                    if predicate(&info) && info.size > best.1 {
+                        best = (id, info.size);
+                    }
+                }
+            }
+            best
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod subtrees_tests {
+    use super::*;
+    use test_support::almost_bst;
+    use trees::*;
+
+    #[test]
+    fn test_checks_at() {
+        let tree = almost_bst();
+
+        if PRINT_TREE {
+            tree.print_visualization_url("Tree inside test_checks_at:");
+        }
+
+        assert_eq!(tree.is_bst(), false);
+        assert_eq!(tree.is_bst_at(1), true);
+        assert_eq!(tree.is_bst_at(2), false);
+        assert_eq!(tree.is_bst_at(5), true);
+
+        assert_eq!(tree.get_height_at(0), 3);
+        assert_eq!(tree.get_height_at(2), 2);
+        assert_eq!(tree.get_height_at(3), 1);
+
+        assert_eq!(tree.sum_at(0), 46);
+        assert_eq!(tree.sum_at(1), 14);
+        assert_eq!(tree.sum_at(2), 22);
+
+        assert_eq!(tree.is_max_heap(), false);
+        assert_eq!(tree.is_max_heap_at(2), false); // not complete
+        assert_eq!(tree.is_max_heap_at(5), true);
+        assert_eq!(tree.is_min_heap_at(5), true);
+    }
+
+    #[test]
+    fn test_balanced_at() {
+        let tree: Tree = Tree::from_level_order("[1,2,null,3,4,5]").unwrap();
+
+        assert_eq!(tree.is_balanced(), false);
+        assert_eq!(tree.is_balanced_at(1), true);
+        assert_eq!(tree.get_height_at(1), 3);
+    }
+
+    #[test]
+    fn test_heap_at() {
+        let tree: Tree = Tree::from_level_order("[1,9,2,7,8,null,3]").unwrap();
+
+        assert_eq!(tree.is_max_heap_at(1), true);
+        assert_eq!(tree.is_max_heap_at(2), false); // 2 has only a right child
+        assert_eq!(tree.is_min_heap_at(2), false);
+        assert_eq!(tree.is_heap_by_at(1, |a, b| a.cmp(b)), true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id is out of range")]
+    fn test_checks_at_out_of_range() {
+        almost_bst().is_bst_at(6);
+    }
+
+    #[test]
+    #[should_panic(expected = "Node id is out of range or a free slot")]
+    fn test_checks_at_free_slot() {
+        let mut tree = almost_bst();
+        tree.remove_subtree(1);
+        // the slot 3 keeps its stale key, but it is not a node anymore
+        tree.is_bst_at(3);
+    }
+
+    #[test]
+    fn test_subtree_infos() {
+        let tree = almost_bst();
+        let infos = tree.subtree_infos();
+
+        let root = infos[0].unwrap();
+        assert_eq!(root.size, 6);
+        assert_eq!(root.height, 3);
+        assert_eq!(root.sum, 46);
+        assert_eq!((root.min, root.max), (1, 15));
+        assert_eq!(root.is_bst, false);
+        assert_eq!(root.is_balanced, true);
+
+        for (id, info_opt) in infos.iter().enumerate() {
+            let info = info_opt.unwrap();
+            assert_eq!(info.size, tree.preorder_at(id).count());
+            assert_eq!(info.height, tree.get_height_at(id));
+            assert_eq!(info.sum, tree.sum_at(id));
+            assert_eq!(info.is_bst, tree.is_bst_at(id));
+            assert_eq!(info.is_balanced, tree.is_balanced_at(id));
+        }
+    }
+
+    #[test]
+    fn test_subtree_infos_free_slots() {
+        let mut tree = almost_bst();
+        tree.remove_subtree(2);
+
+        let infos = tree.subtree_infos();
+        assert_eq!(infos[2], None);
+        assert_eq!(infos[5], None);
+        assert_eq!(infos[0].unwrap().size, 4);
+        assert_eq!(infos[0].unwrap().is_bst, true);
+    }
+
+    #[test]
+    fn test_largest_bst_subtree() {
+        let tree = almost_bst();
+        assert_eq!(tree.largest_bst_subtree(), (1, 3));
+        assert_eq!(tree.largest_balanced_subtree(), (0, 6));
+
+        // a whole BST
+        let tree: Tree = Tree::from_level_order("[4,2,6,1,3,5,7]").unwrap();
+        assert_eq!(tree.largest_bst_subtree(), (0, 7));
+
+        // the larger BST is in the right subtree, which is not balanced
+        let tree: Tree = Tree::from_level_order("[10,11,20,null,null,15,null,12]").unwrap();
+        assert_eq!(tree.largest_bst_subtree(), (2, 3));
+        assert_eq!(tree.largest_balanced_subtree(), (3, 2));
+
+        let tree = Tree::with_root(1);
+        assert_eq!(tree.largest_bst_subtree(), (0, 1));
+    }
+}
+
+
+
+
+
+
+
+
+
+/// # Random trees
+///
+/// Seeded generators of random trees, to cross-check the methods of the exercises against simple (brute-force) oracles.
+/// The same seed always gives the same trees, so a failing case can be reproduced.
+///
+/// The generators build trees with `size` nodes (a tree always has a root, so a `size` of 0 gives a single node)
+/// and `u32` keys in `0..key_bound`. `mutate` changes a tree by a small step,
+/// to get trees that are "almost" BSTs, balanced trees or heaps.
+mod random_trees {
+
+    use crate::avl::AvlTree;
+    use crate::trees;
+
+    /// A small pseudo-random number generator (SplitMix64)
+    pub struct TreeRng {
+        state: u64,
+    }
+
+    impl TreeRng {
+        pub fn new(seed: u64) -> Self {
+            Self { state: seed }
+        }
+
+        pub fn next_u64(&mut self) -> u64 {
+            self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+            let mut z = self.state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+            z ^ (z >> 31)
+        }
+
+        /// a random number in `0..bound` (`bound` must be positive)
+        pub fn below(&mut self, bound: usize) -> usize {
+            (self.next_u64() % bound as u64) as usize
+        }
+
+        /// a random key in `0..key_bound`
+        pub fn key(&mut self, key_bound: u32) -> u32 {
+            (self.next_u64() % key_bound as u64) as u32
+        }
+    }
+
+    /// A change made by `mutate`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Mutation {
+        /// the key of the node `id` was replaced by a random key
+        ChangeKey { id: usize },
+        /// the keys of the node `id` and of its parent were swapped
+        SwapWithParent { id: usize },
+        /// the leaf `id` was removed
+        RemoveLeaf { id: usize },
+        /// a new leaf `id` was added in a random free child slot
+        AddLeaf { id: usize },
+    }
+
+    impl trees::Tree<u32> {
+
+        /// # Returns
+        /// a tree with `size` nodes and a random shape: every new node takes a random free child slot
+        pub fn random_tree(rng: &mut TreeRng, size: usize, key_bound: u32) -> Self {
+            let mut tree = Self::with_root(rng.key(key_bound));
+            let mut free_slots = vec![(0, true), (0, false)];
+
+            for _ in 1..size {
+                let (parent_id, is_left) = free_slots.swap_remove(rng.below(free_slots.len()));
+                let id = tree.add_node(parent_id, rng.key(key_bound), is_left);
+                free_slots.push((id, true));
+                free_slots.push((id, false));
+            }
+            tree
+        }
+
+        /// # Returns
+        /// a BST with `size` random keys, inserted in random order
+        pub fn random_bst(rng: &mut TreeRng, size: usize, key_bound: u32) -> Self {
+            let mut tree = Self::with_root(rng.key(key_bound));
+            for _ in 1..size {
+                tree.bst_insert(rng.key(key_bound));
+            }
+            tree
+        }
+
+        /// # Returns
+        /// an AVL tree (so a balanced BST) with `size` random keys
+        pub fn random_avl(rng: &mut TreeRng, size: usize, key_bound: u32) -> Self {
+            let mut avl = AvlTree::with_root(rng.key(key_bound));
+            for _ in 1..size {
+                avl.insert(rng.key(key_bound));
+            }
+            avl.into_tree()
+        }
+
+        /// # Returns
+        /// a complete tree with `size` random keys
+        pub fn random_complete(rng: &mut TreeRng, size: usize, key_bound: u32) -> Self {
+            let keys: Vec<u32> = (0..size.max(1)).map(|_| rng.key(key_bound)).collect();
+            Self::from_heap_array(&keys)
+        }
+
+        /// # Returns
+        /// a max-heap with `size` random keys
+        pub fn random_max_heap(rng: &mut TreeRng, size: usize, key_bound: u32) -> Self {
+            let mut tree = Self::random_complete(rng, size, key_bound);
+            tree.heapify();
+            tree
+        }
+
+        /// Applies a random small change to the tree. A leaf is removed only if the tree has more than one node.
+        ///
+        /// # Returns
+        /// the change that was applied
+        pub fn mutate(&mut self, rng: &mut TreeRng, key_bound: u32) -> Mutation {
+            let parents = self.parents();
+            let ids: Vec<usize> = self.preorder().map(|(id, _)| id).collect();
+            let id = ids[rng.below(ids.len())];
+
+            match rng.below(4) {
+                0 => {
+                    self.nodes[id].key = rng.key(key_bound);
+                    Mutation::ChangeKey { id }
+                }
+                1 if id != 0 => {
+                    self.swap_keys(id, parents[id].unwrap());
+                    Mutation::SwapWithParent { id }
+                }
+                2 if ids.len() > 1 => {
+                    let leaves: Vec<usize> = ids
+                        .iter()
+                        .copied()
+                        .filter(|&id| id != 0 && self.nodes[id].id_left.is_none() && self.nodes[id].id_right.is_none())
+                        .collect();
+                    let leaf_id = leaves[rng.below(leaves.len())];
+                    self.remove_subtree(leaf_id);
+                    Mutation::RemoveLeaf { id: leaf_id }
+                }
+                _ => {
+                    let free_slots: Vec<(usize, bool)> = ids
+                        .iter()
+                        .flat_map(|&id| [(id, true), (id, false)])
+                        .filter(|&(id, is_left)| {
+                            let node = &self.nodes[id];
+                            if is_left { node.id_left.is_none() } else { node.id_right.is_none() }
+                        })
+                        .collect();
+                    let (parent_id, is_left) = free_slots[rng.below(free_slots.len())];
+                    let new_id = self.add_node(parent_id, rng.key(key_bound), is_left);
+                    Mutation::AddLeaf { id: new_id }
+                }
+            }
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod random_trees_tests {
+    use super::*;
+    use random_trees::TreeRng;
+    use test_support::assert_tree;
+    use trees::*;
+
+    const CASES: usize = 2000;
+    const MAX_SIZE: usize = 40;
+
+    /// brute force: a tree is a BST (with equal keys on either side) iff its keys are sorted in in-order
+    fn oracle_is_bst(tree: &Tree) -> bool {
+        let keys: Vec<u32> = tree.inorder().map(|(_, node)| node.key).collect();
+        keys.windows(2).all(|pair| pair[0] <= pair[1])
+    }
+
+    fn oracle_height(tree: &Tree, id_opt: Option<usize>) -> u32 {
+        match id_opt {
+            None => 0,
+            Some(id) => {
+                let node = tree.get_node(id).unwrap();
+                1 + oracle_height(tree, node.id_left).max(oracle_height(tree, node.id_right))
+            }
+        }
+    }
+
+    /// brute force: the heights of the children are computed from scratch for every node
+    fn oracle_is_balanced(tree: &Tree) -> bool {
+        tree.preorder().all(|(_, node)| {
+            oracle_height(tree, node.id_left).abs_diff(oracle_height(tree, node.id_right)) <= 1
+        })
+    }
+
+    /// brute force: a tree is complete iff its level-order array has no hole,
+    /// and a complete tree is a max-heap iff no child is greater than its parent
+    fn oracle_is_max_heap(tree: &Tree) -> bool {
+        let is_complete = tree.to_level_order_values().iter().all(Option::is_some);
+        let is_ordered = tree.preorder().all(|(_, node)| {
+            [node.id_left, node.id_right]
+                .into_iter()
+                .flatten()
+                .all(|child_id| tree.get_node(child_id).unwrap().key <= node.key)
+        });
+        is_complete && is_ordered
+    }
+
+    /// on a mismatch, the SVG image of the tree is written by `assert_tree`
+    fn check_against_oracles(tree: &Tree) {
+        assert_tree(tree, tree.is_bst() == oracle_is_bst(tree), "is_bst");
+        assert_tree(tree, tree.is_balanced() == oracle_is_balanced(tree), "is_balanced");
+        assert_tree(tree, tree.get_height() == oracle_height(tree, Some(0)), "get_height");
+        assert_tree(tree, tree.is_max_heap() == oracle_is_max_heap(tree), "is_max_heap");
+    }
+
+    #[test]
+    fn test_rng_is_seeded() {
+        let first_values = |seed: u64| {
+            let mut rng = TreeRng::new(seed);
+            (0..5).map(|_| rng.next_u64()).collect::<Vec<u64>>()
+        };
+
+        assert_eq!(first_values(1), first_values(1));
+        assert_ne!(first_values(1), first_values(2));
+
+        let tree_of = |seed: u64| Tree::random_tree(&mut TreeRng::new(seed), 20, 100).to_level_order();
+        assert_eq!(tree_of(5), tree_of(5));
+    }
+
+    #[test]
+    fn test_generators() {
+        let mut rng = TreeRng::new(2024);
+
+        for _ in 0..CASES {
+            let size = 1 + rng.below(MAX_SIZE);
+
+            let tree = Tree::random_tree(&mut rng, size, 100);
+            assert_eq!(tree.node_count(), size);
+            assert_eq!(tree.validate(), Ok(()));
+
+            let tree = Tree::random_bst(&mut rng, size, 100);
+            assert_eq!(tree.node_count(), size);
+            assert!(oracle_is_bst(&tree));
+
+            let tree = Tree::random_avl(&mut rng, size, 100);
+            assert_eq!(tree.node_count(), size);
+            assert!(oracle_is_bst(&tree) && oracle_is_balanced(&tree));
+
+            let tree = Tree::random_complete(&mut rng, size, 100);
+            assert!(tree.is_complete());
+
+            let tree = Tree::random_max_heap(&mut rng, size, 100);
+            assert!(oracle_is_max_heap(&tree));
+        }
+    }
+
+    #[test]
+    fn test_checks_against_oracles() {
+        let mut rng = TreeRng::new(7);
+
+        for _ in 0..CASES {
+            let size = 1 + rng.below(MAX_SIZE);
+            // small keys, so that equal keys are common
+            let key_bound = 1 + rng.key(20);
+
+            let trees = [
+                Tree::random_tree(&mut rng, size, key_bound),
+                Tree::random_bst(&mut rng, size, key_bound),
+                Tree::random_avl(&mut rng, size, key_bound),
+                Tree::random_max_heap(&mut rng, size, key_bound),
+            ];
+
+            for mut tree in trees {
+                check_against_oracles(&tree);
+
+                // a few small changes make the valid trees "almost" valid
+                for _ in 0..3 {
+                    tree.mutate(&mut rng, key_bound);
+                    assert_eq!(tree.validate(), Ok(()));
+                    check_against_oracles(&tree);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_mutations_break_the_properties() {
+        let mut rng = TreeRng::new(99);
+        let (mut not_bst, mut not_balanced, mut not_heap) = (0, 0, 0);
+
+        for _ in 0..CASES {
+            let size = 2 + rng.below(MAX_SIZE);
+
+            let mut tree = Tree::random_avl(&mut rng, size, 1000);
+            tree.mutate(&mut rng, 1000);
+            not_bst += (!tree.is_bst()) as usize;
+            not_balanced += (!tree.is_balanced()) as usize;
+
+            let mut tree = Tree::random_max_heap(&mut rng, size, 1000);
+            tree.mutate(&mut rng, 1000);
+            not_heap += (!tree.is_max_heap()) as usize;
+        }
+
+        // the "almost valid" trees are not all valid
+        assert!(not_bst > CASES / 10, "{}", not_bst);
+        assert!(not_balanced > 0, "{}", not_balanced);
+        assert!(not_heap > CASES / 10, "{}", not_heap);
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Fenwick tree from the segment trees hands-on, used by `SubtreeAggregator`
+mod fenwick;
+
+/// # Euler tour and subtree queries
+///
+/// Visiting the tree in preorder, the nodes of every subtree are contiguous: the subtree rooted at `id`
+/// is made of the positions `tin(id)..=tout(id)` of the visit. So a subtree query on the tree becomes a range query
+/// on an array, and `SubtreeAggregator` answers it with a Fenwick tree.
+///
+/// Like `Lca`, both structures are snapshots of the tree: they have to be rebuilt after the shape of the tree changes.
+mod euler_tour {
+
+    use crate::fenwick::FenwickTree;
+    use crate::trees;
+
+    pub struct EulerTour {
+        /// the node ids in preorder
+        order: Vec<usize>,
+        /// `tin[id]` is the position of `id` in `order`, `None` if `id` is not in the tree
+        tin: Vec<Option<usize>>,
+        /// `tout[id]` is the position in `order` of the last node of the subtree rooted at `id`
+        tout: Vec<Option<usize>>,
+    }
+
+    impl EulerTour {
+        pub fn new<K>(tree: &trees::Tree<K>) -> Self {
+            let order: Vec<usize> = tree.preorder().map(|(id, _)| id).collect();
+
+            let mut tin = vec![None; tree.nodes.len()];
+            for (position, &id) in order.iter().enumerate() {
+                tin[id] = Some(position);
+            }
+
+            // in reverse preorder, the children are visited before their parent
+            let mut sizes = vec![0; tree.nodes.len()];
+            let mut tout = vec![None; tree.nodes.len()];
+            for &id in order.iter().rev() {
+                let node = &tree.nodes[id];
+                sizes[id] = 1 + [node.id_left, node.id_right].into_iter().flatten().map(|child_id| sizes[child_id]).sum::<usize>();
+                tout[id] = tin[id].map(|position| position + sizes[id] - 1);
+            }
+
+            Self { order, tin, tout }
+        }
+
+        /// # Returns
+        /// the node ids in the order of the visit
+        pub fn order(&self) -> &[usize] {
+            &self.order
+        }
+
+        /// # Returns
+        /// the position of `id` in the visit, or `None` if `id` is not a node of the tree
+        pub fn tin(&self, id: usize) -> Option<usize> {
+            *self.tin.get(id)?
+        }
+
+        /// # Returns
+        /// the position of the last node of the subtree rooted at `id`, or `None` if `id` is not a node of the tree
+        pub fn tout(&self, id: usize) -> Option<usize> {
+            *self.tout.get(id)?
+        }
+
+        /// # Returns
+        /// true iff `ancestor` is an ancestor of `id` (or `id` itself)
+        pub fn is_ancestor(&self, ancestor: usize, id: usize) -> bool {
+            match (self.tin(ancestor), self.tout(ancestor), self.tin(id)) {
+                (Some(tin_ancestor), Some(tout_ancestor), Some(tin_id)) => tin_ancestor <= tin_id && tin_id <= tout_ancestor,
+                _ => false,
+            }
+        }
+    }
+
+    /// Point updates to the keys and sums over subtrees, in `O(log n)` time each.
+    /// The keys are copied from the tree when the aggregator is built, and the updates do not change the tree.
+    pub struct SubtreeAggregator {
+        tour: EulerTour,
+        /// the key of the node `tour.order[i]` is at position `i`
+        fenwick: FenwickTree,
+    }
+
+    impl SubtreeAggregator {
+        pub fn new<K: Copy + Into<i64>>(tree: &trees::Tree<K>) -> Self {
+            let tour = EulerTour::new(tree);
+            let mut fenwick = FenwickTree::with_len(tour.order().len());
+            for (position, &id) in tour.order().iter().enumerate() {
+                fenwick.add(position, tree.nodes[id].key.into());
+            }
+            Self { tour, fenwick }
+        }
+
+        pub fn tour(&self) -> &EulerTour {
+            &self.tour
+        }
+
+        /// Adds `delta` to the key of the node `id`
+        pub fn add(&mut self, id: usize, delta: i64) -> Result<(), trees::TreeError> {
+            let position = self.tour.tin(id).ok_or(trees::TreeError::MissingNode { id })?;
+            self.fenwick.add(position, delta);
+            Ok(())
+        }
+
+        /// # Returns
+        /// the current key of the node `id`, or `None` if `id` is not a node of the tree
+        pub fn key(&self, id: usize) -> Option<i64> {
+            let position = self.tour.tin(id)?;
+            Some(self.fenwick.range_sum(position, position))
+        }
+
+        /// # Returns
+        /// the sum of the current keys of the subtree rooted at `id`, or `None` if `id` is not a node of the tree
+        pub fn subtree_sum(&self, id: usize) -> Option<i64> {
+            Some(self.fenwick.range_sum(self.tour.tin(id)?, self.tour.tout(id)?))
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod euler_tour_tests {
+    use super::*;
+    use euler_tour::{EulerTour, SubtreeAggregator};
+    use random_trees::TreeRng;
+    use test_support::sample_tree;
+    use trees::*;
+
+    #[test]
+    fn test_euler_tour() {
+        let tree = sample_tree();
+        let tour = EulerTour::new(&tree);
+
+        assert_eq!(tour.order(), &[0, 1, 3, 4, 6, 2, 5]);
+        assert_eq!((tour.tin(0), tour.tout(0)), (Some(0), Some(6)));
+        assert_eq!((tour.tin(1), tour.tout(1)), (Some(1), Some(4)));
+        assert_eq!((tour.tin(3), tour.tout(3)), (Some(2), Some(2)));
+        assert_eq!((tour.tin(2), tour.tout(2)), (Some(5), Some(6)));
+        assert_eq!(tour.tin(7), None);
+
+        assert!(tour.is_ancestor(0, 5));
+        assert!(tour.is_ancestor(1, 6));
+        assert!(tour.is_ancestor(4, 4));
+        assert!(tour.is_ancestor(1, 5) == false);
+        assert!(tour.is_ancestor(3, 1) == false);
+    }
+
+    #[test]
+    fn test_euler_tour_free_slots() {
+        let mut tree = sample_tree();
+        tree.remove_subtree(1);
+        let tour = EulerTour::new(&tree);
+
+        assert_eq!(tour.order(), &[0, 2, 5]);
+        assert_eq!(tour.tin(3), None);
+        assert_eq!(tour.tout(0), Some(2));
+    }
+
+    #[test]
+    fn test_subtree_aggregator() {
+        let tree = sample_tree();
+        let mut aggregator = SubtreeAggregator::new(&tree);
+
+        assert_eq!(aggregator.subtree_sum(0), Some(28));
+        assert_eq!(aggregator.subtree_sum(1), Some(18));
+        assert_eq!(aggregator.subtree_sum(2), Some(9));
+
+        aggregator.add(4, -10).unwrap();
+        assert_eq!(aggregator.key(4), Some(-5));
+        assert_eq!(aggregator.subtree_sum(1), Some(8));
+        assert_eq!(aggregator.subtree_sum(2), Some(9));
+        assert_eq!(aggregator.subtree_sum(0), Some(18));
+
+        // the tree is not changed
+        assert_eq!(tree.sum(), 28);
+
+        assert_eq!(aggregator.add(7, 1), Err(TreeError::MissingNode { id: 7 }));
+        assert_eq!(aggregator.subtree_sum(7), None);
+    }
+
+    #[test]
+    fn test_subtree_aggregator_random() {
+        let mut rng = TreeRng::new(21);
+
+        for _ in 0..100 {
+            let size = 1 + rng.below(200);
+            let tree = Tree::random_tree(&mut rng, size, 1000);
+            let mut keys: Vec<i64> = tree.nodes.iter().map(|node| node.key as i64).collect();
+            let mut aggregator = SubtreeAggregator::new(&tree);
+
+            for _ in 0..200 {
+                let id = rng.below(size);
+                if rng.below(2) == 0 {
+                    let delta = rng.below(2001) as i64 - 1000;
+                    keys[id] += delta;
+                    aggregator.add(id, delta).unwrap();
+                } else {
+                    let expected: i64 = tree.preorder_at(id).map(|(descendant_id, _)| keys[descendant_id]).sum();
+                    assert_eq!(aggregator.subtree_sum(id), Some(expected));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_subtree_aggregator_signed_keys() {
+        let tree: Tree<i32> = Tree::from_level_order("[-1,-2,3]").unwrap();
+        let aggregator = SubtreeAggregator::new(&tree);
+        assert_eq!(aggregator.subtree_sum(0), Some(0));
+        assert_eq!(aggregator.subtree_sum(1), Some(-2));
+    }
+}
+
+
+
+
+
+
+
+
+
+/// Segment tree of exercise 1 of the segment trees hands-on, used by `HeavyLight`
+mod ex_1_segment;
+
+/// # Heavy-light decomposition
+///
+/// The heavy child of a node is the child with the largest subtree. Following heavy children splits the tree into chains,
+/// and any path from a node to the root crosses `O(log n)` chains, because every light edge at least halves the size
+/// of the subtree.
+///
+/// The nodes are numbered visiting the heavy child first, so every chain is a contiguous range of positions:
+/// a path between two nodes is then `O(log n)` ranges of the range-max `SegmentTree` of exercise 1.
+///
+/// The segment tree has two range updates: adding a value to every key (`path_add`, lazy, in `O(log n)` per range),
+/// and replacing every key `k` with `min(k, value)` (`path_chmin`, which goes down to every position of the range).
+/// The keys are `i64`, so that the additions do not overflow the `i32` keys of a tree.
+/// Like `Lca`, the decomposition is a snapshot of the tree, and the updates do not change the tree.
+mod hld {
+
+    use crate::ex_1_segment::SegmentTree;
+    use crate::trees;
+
+    pub struct HeavyLight {
+        /// `parents[id]` is the parent of `id` (`None` for the root and for free slots)
+        parents: Vec<Option<usize>>,
+        depths: Vec<usize>,
+        /// `heads[id]` is the first (shallowest) node of the chain of `id`
+        heads: Vec<usize>,
+        /// `positions[id]` is the position of `id` (from 0), `None` if `id` is not in the tree
+        positions: Vec<Option<usize>>,
+        /// the keys by position: the position `p` is `p + 1` in the segment tree, whose positions start from 1
+        segment_tree: SegmentTree<i64>,
+    }
+
+    impl HeavyLight {
+        pub fn new<K: Copy + Into<i64>>(tree: &trees::Tree<K>) -> Self {
+            let n = tree.nodes.len();
+            let parents = tree.parents();
+
+            let mut depths = vec![0; n];
+            let mut level_order = Vec::new();
+            for (id, _, depth) in tree.level_order() {
+                depths[id] = depth;
+                level_order.push(id);
+            }
+
+            // in reverse level order, the children are visited before their parent
+            let mut sizes = vec![0; n];
+            for &id in level_order.iter().rev() {
+                let node = &tree.nodes[id];
+                sizes[id] = 1 + [node.id_left, node.id_right].into_iter().flatten().map(|child_id| sizes[child_id]).sum::<usize>();
+            }
+
+            let mut heads = vec![0; n];
+            let mut positions = vec![None; n];
+            let mut keys = Vec::with_capacity(level_order.len());
+
+            // the heavy child is pushed last, so that it is visited right after its parent
+            let mut stack = vec![0];
+            while let Some(id) = stack.pop() {
+                positions[id] = Some(keys.len());
+                keys.push(tree.nodes[id].key.into());
+
+                let node = &tree.nodes[id];
+                let mut children: Vec<usize> = [node.id_left, node.id_right].into_iter().flatten().collect();
+                children.sort_by_key(|&child_id| sizes[child_id]);
+                for (index, &child_id) in children.iter().enumerate() {
+                    let is_heavy = index == children.len() - 1;
+                    heads[child_id] = if is_heavy { heads[id] } else { child_id };
+                    stack.push(child_id);
+                }
+            }
+
+            let mut segment_tree = SegmentTree::new(keys.len());
+            segment_tree.initialize(&keys);
+
+            Self { parents, depths, heads, positions, segment_tree }
+        }
+
+        /// # Returns
+        /// the ranges of positions (first and last, both included) that cover the path between `a` and `b`,
+        /// or `None` if one of them is not a node of the tree
+        pub fn path_ranges(&self, a: usize, b: usize) -> Option<Vec<(usize, usize)>> {
+            let (mut a, mut b) = (a, b);
+            self.positions.get(a).copied()??;
+            self.positions.get(b).copied()??;
+
+            let mut ranges = Vec::new();
+            while self.heads[a] != self.heads[b] {
+                // move up from the node whose chain starts deeper
+                if self.depths[self.heads[a]] < self.depths[self.heads[b]] {
+                    std::mem::swap(&mut a, &mut b);
+                }
+                let head = self.heads[a];
+                ranges.push((self.positions[head]?, self.positions[a]?));
+                a = self.parents[head]?;
+            }
+
+            // `a` and `b` are now on the same chain
+            let (position_a, position_b) = (self.positions[a]?, self.positions[b]?);
+            ranges.push((position_a.min(position_b), position_a.max(position_b)));
+
+            Some(ranges)
+        }
+
+        /// # Returns
+        /// the maximum key on the path between `a` and `b` (both included),
+        /// or `None` if one of them is not a node of the tree
+        pub fn path_max(&self, a: usize, b: usize) -> Option<i64> {
+            let ranges = self.path_ranges(a, b)?;
+            ranges.into_iter().map(|(first, last)| self.segment_tree.query(first + 1, last + 1)).max()
+        }
+
+        /// Adds `delta` to every key on the path between `a` and `b` (both included)
+        pub fn path_add(&mut self, a: usize, b: usize, delta: i64) -> Result<(), trees::TreeError> {
+            for (first, last) in self.checked_path_ranges(a, b)? {
+                self.segment_tree.add(first + 1, last + 1, delta);
+            }
+            Ok(())
+        }
+
+        /// Replaces every key `k` on the path between `a` and `b` (both included) with `min(k, value)`
+        pub fn path_chmin(&mut self, a: usize, b: usize, value: i64) -> Result<(), trees::TreeError> {
+            for (first, last) in self.checked_path_ranges(a, b)? {
+                self.segment_tree.update(first + 1, last + 1, value);
+            }
+            Ok(())
+        }
+
+        /// Same as `path_ranges`, with an error naming the node that is not in the tree
+        fn checked_path_ranges(&self, a: usize, b: usize) -> Result<Vec<(usize, usize)>, trees::TreeError> {
+            let missing = if self.positions.get(a).copied().flatten().is_none() { a } else { b };
+            self.path_ranges(a, b).ok_or(trees::TreeError::MissingNode { id: missing })
+        }
+
+        /// # Returns
+        /// the current key of `id`, or `None` if `id` is not a node of the tree
+        pub fn key(&self, id: usize) -> Option<i64> {
+            let position = self.positions.get(id).copied()??;
+            Some(self.segment_tree.query(position + 1, position + 1))
+        }
+    }
+}
+
+
+
+#[cfg(test)]
+mod hld_tests {
+    use super::*;
+    use hld::HeavyLight;
+    use lca::Lca;
+    use random_trees::TreeRng;
+    use test_support::signed_tree;
+    use trees::*;
+
+    /// the nodes on the path between `a` and `b`, computed by walking up from both to their LCA
+    fn naive_path<K>(tree: &Tree<K>, a: usize, b: usize) -> Vec<usize> {
+        let parents = tree.parents();
+        let lca = Lca::new(tree).lca(a, b).unwrap();
+
+        let mut path = Vec::new();
+        for start in [a, b] {
+            let mut curr_id = start;
+            while curr_id != lca {
+                path.push(curr_id);
+                curr_id = parents[curr_id].unwrap();
+            }
+        }
+        path.push(lca);
+        path
+    }
+
+    #[test]
+    fn test_path_ranges() {
+        let tree = signed_tree();
+        let hld = HeavyLight::new(&tree);
+
+        // the heavy path 0 1 4 6 is the first chain
+        assert_eq!(hld.path_ranges(6, 0), Some(vec![(0, 3)]));
+        assert_eq!(hld.path_ranges(4, 4), Some(vec![(2, 2)]));
+
+        let ranges = hld.path_ranges(3, 5).unwrap();
+        assert_eq!(ranges.len(), 3);
+        let covered: usize = ranges.iter().map(|(first, last)| last - first + 1).sum();
+        assert_eq!(covered, naive_path(&tree, 3, 5).len());
+
+        assert_eq!(hld.path_ranges(0, 8), None);
+    }
+
+    #[test]
+    fn test_path_max() {
+        let tree = signed_tree();
+        let hld = HeavyLight::new(&tree);
+
+        assert_eq!(hld.path_max(6, 5), Some(20));
+        assert_eq!(hld.path_max(3, 4), Some(8));
+        assert_eq!(hld.path_max(4, 5), Some(10));
+        assert_eq!(hld.path_max(1, 4), Some(-2));
+        assert_eq!(hld.path_max(0, 8), None);
+    }
+
+    #[test]
+    fn test_path_chmin() {
+        let tree = signed_tree();
+        let mut hld = HeavyLight::new(&tree);
+
+        hld.path_chmin(3, 6, 0).unwrap();
+        assert_eq!(hld.path_max(3, 6), Some(0));
+        assert_eq!(hld.key(3), Some(0));
+        assert_eq!(hld.key(6), Some(0));
+        assert_eq!(hld.key(1), Some(-2));
+        // the root and the other nodes are not on the path
+        assert_eq!(hld.key(0), Some(10));
+        assert_eq!(hld.key(5), Some(-1));
+        assert_eq!(hld.path_max(6, 2), Some(10));
+
+        assert_eq!(hld.path_chmin(0, 9, 0), Err(TreeError::MissingNode { id: 9 }));
+    }
+
+    #[test]
+    fn test_path_add() {
+        let tree = signed_tree();
+        let mut hld = HeavyLight::new(&tree);
+
+        // the path 3 1 4 6 crosses two chains
+        hld.path_add(3, 6, 10).unwrap();
+        for (id, key) in [(3, 18), (1, 8), (4, 6), (6, 30)] {
+            assert_eq!(hld.key(id), Some(key));
+        }
+        assert_eq!(hld.key(0), Some(10));
+        assert_eq!(hld.key(5), Some(-1));
+        assert_eq!(hld.path_max(6, 0), Some(30));
+        assert_eq!(hld.path_max(0, 5), Some(10));
+
+        // the additions and the chmin updates compose
+        hld.path_chmin(6, 3, 12).unwrap();
+        hld.path_add(6, 5, -3).unwrap();
+        assert_eq!(hld.key(3), Some(12));
+        assert_eq!(hld.key(6), Some(9));
+        assert_eq!(hld.key(2), Some(4));
+        assert_eq!(hld.path_max(3, 5), Some(12));
+
+        // the keys are i64: the sums do not overflow the i32 keys of the tree
+        hld.path_add(0, 0, i32::MAX as i64).unwrap();
+        assert_eq!(hld.path_max(0, 6), Some(i32::MAX as i64 + 7));
+
+        assert_eq!(hld.path_add(9, 0, 1), Err(TreeError::MissingNode { id: 9 }));
+    }
+
+    #[test]
+    fn test_hld_random() {
+        let mut rng = TreeRng::new(22);
+
+        for _ in 0..50 {
+            let size = 1 + rng.below(300);
+            let tree = Tree::random_tree(&mut rng, size, 1000);
+            let mut keys: Vec<i64> = tree.nodes.iter().map(|node| i64::from(node.key)).collect();
+            let mut hld = HeavyLight::new(&tree);
+
+            for _ in 0..100 {
+                let (a, b) = (rng.below(size), rng.below(size));
+                let path = naive_path(&tree, a, b);
+
+                // every path is split in O(log n) ranges
+                assert!(hld.path_ranges(a, b).unwrap().len() <= 2 * (usize::BITS - size.leading_zeros()) as usize);
+
+                match rng.below(4) {
+                    0 => {
+                        let value = rng.key(1000) as i64;
+                        hld.path_chmin(a, b, value).unwrap();
+                        for id in path {
+                            keys[id] = keys[id].min(value);
+                        }
+                    }
+                    1 => {
+                        let delta = rng.key(1000) as i64 - 500;
+                        hld.path_add(a, b, delta).unwrap();
+                        for id in path {
+                            keys[id] += delta;
+                        }
+                    }
+                    _ => {
+                        let expected = path.iter().map(|&id| keys[id]).max();
+                        assert_eq!(hld.path_max(a, b), expected);
+                    }
+                }
+            }
+
+            for (id, key) in keys.iter().enumerate() {
+                assert_eq!(hld.key(id), Some(*key));
+            }
+        }
+    }
+}
+
+
+
+
+
+
+
+
+
+/// # Morris in-order traversal
+///
+/// The recursive helpers use `O(h)` stack frames, and the iterators use an explicit stack of `O(h)` ids.
+/// The Morris traversal uses `O(1)` extra space: before going down to the left subtree of a node, it links
+/// the rightmost node of that subtree (the in-order predecessor) to the node, with a temporary `id_right` "thread".
+/// The thread is followed to come back up, and removed at that moment.
+///
+/// The traversal takes `O(n)` time (every edge is walked at most 3 times), and when it ends every thread has been removed,
+/// so the tree is exactly as before. It needs `&mut self` because of the threads, and it assumes a valid tree (see `validate()`).
+mod morris {
+
+    use crate::trees;
+
+    impl<K> trees::Tree<K> {
+
+        /// Calls `visit(id, key)` for every node, in in-order, using `O(1)` extra space
+        pub fn morris_inorder<F>(&mut self, mut visit: F)
+        where
+            F: FnMut(usize, &K),
+        {
+            self.morris_inorder_ids(|tree, id| visit(id, &tree.nodes[id].key));
+        }
+
+        /// Calls `visit(tree, id)` for every node, in in-order.
+        /// The tree can be read by `visit`, but it contains the threads of the nodes whose left subtree is being visited.
+        fn morris_inorder_ids<F>(&mut self, mut visit: F)
+        where
+            F: FnMut(&Self, usize),
+        {
+            let mut curr_id_opt = self.get_node(0).map(|_| 0);
+
+            while let Some(curr_id) = curr_id_opt {
+                let Some(left_id) = self.nodes[curr_id].id_left else {
+                    // no left subtree: visit the node and go right (possibly following a thread)
+                    visit(self, curr_id);
+                    curr_id_opt = self.nodes[curr_id].id_right;
+                    continue;
+                };
+
+                // the in-order predecessor is the rightmost node of the left subtree, unless a thread to the current node is found first
+                let mut predecessor_id = left_id;
+                while let Some(right_id) = self.nodes[predecessor_id].id_right {
+                    if right_id == curr_id {
+                        break;
+                    }
+                    predecessor_id = right_id;
+                }
 
+                if self.nodes[predecessor_id].id_right == Some(curr_id) {
+                    // the left subtree has been visited: remove the thread, visit the node and go right
+                    self.nodes[predecessor_id].id_right = None;
+                    visit(self, curr_id);
+                    curr_id_opt = self.nodes[curr_id].id_right;
+                } else {
+                    // first time here: add the thread and visit the left subtree
+                    self.nodes[predecessor_id].id_right = Some(curr_id);
+                    curr_id_opt = Some(left_id);
+                }
+            }
+        }
+    }
+
+    impl<K: Ord> trees::Tree<K> {
+
+        /// Same as `is_bst`, using `O(1)` extra space: a tree is a BST iff its keys are sorted in in-order.
+        /// The traversal always runs to the end, so that all the threads are removed.
+        pub fn is_bst_morris(&mut self) -> bool {
+            let mut is_sorted = true;
+            let mut prev_id_opt: Option<usize> = None;
+
+            self.morris_inorder_ids(|tree, id| {
+                if let Some(prev_id) = prev_id_opt {
+                    is_sorted &= tree.nodes[prev_id].key <= tree.nodes[id].key;
+                }
+                prev_id_opt = Some(id);
+            });
 
-
+            is_sorted
+        }
+    }
+}
 
 
 
 #[cfg(test)]
-mod max_heap_tests {
+mod morris_tests {
     use super::*;
+    use random_trees::TreeRng;
     use trees::*;
 
     #[test]
-    fn test_single_node_max_heap() {
-        let tree = Tree::with_root(10);
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+    fn test_morris_inorder() {
+        let mut tree: Tree = Tree::from_level_order("[4,2,6,1,3,5,7,null,null,null,null,null,null,null,8]").unwrap();
+        let expected: Vec<(usize, u32)> = tree.inorder().map(|(id, node)| (id, node.key)).collect();
+        let json_before = tree.to_json();
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_single_node_max_heap:");
-        }
+        let mut visited = Vec::new();
+        tree.morris_inorder(|id, key| visited.push((id, *key)));
 
-        assert_eq!(is_max_heap, true);
-        assert_eq!(max_value, 10);
+        assert_eq!(visited, expected);
+        assert_eq!(visited.iter().map(|(_, key)| *key).collect::<Vec<u32>>(), (1..=8).collect::<Vec<u32>>());
+
+        // all the threads have been removed
+        assert_eq!(tree.to_json(), json_before);
     }
 
     #[test]
-    fn test_two_nodes_max_heap() {
-        let mut tree = Tree::with_root(10);
-        tree.add_node(0, 9, true);
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+    fn test_morris_single_node() {
+        let mut tree = Tree::with_root(1);
+        let mut visited = Vec::new();
+        tree.morris_inorder(|id, _| visited.push(id));
+        assert_eq!(visited, vec![0]);
+    }
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_two_nodes_max_heap:");
-        }
+    #[test]
+    fn test_is_bst_morris() {
+        let mut tree: Tree = Tree::from_level_order("[10,5,20,null,12]").unwrap();
+        assert_eq!(tree.is_bst_morris(), false);
+        assert_eq!(tree.to_level_order(), "[10,5,20,null,12]");
 
-        assert_eq!(is_max_heap, true);
-        assert_eq!(max_value, 10);
+        let mut tree: Tree = Tree::from_level_order("[10,5,20,5,10,20]").unwrap();
+        assert_eq!(tree.is_bst_morris(), true);
     }
 
     #[test]
-    fn test_max_heap_with_left_subtree_unbalanced() {
-        let mut tree = Tree::with_root(10);
-        let last_id = tree.add_node(0, 10, true);
-        tree.add_node(last_id, 5, true);
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+    fn test_morris_random() {
+        let mut rng = TreeRng::new(23);
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_max_heap_with_left_subtree_unbalanced:");
-        }
+        for _ in 0..500 {
+            let size = 1 + rng.below(60);
+            let mut trees = [Tree::random_tree(&mut rng, size, 20), Tree::random_bst(&mut rng, size, 20)];
 
-        assert_eq!(is_max_heap, false);
-        assert_eq!(max_value, 10);
+            for tree in trees.iter_mut() {
+                if rng.below(2) == 0 {
+                    tree.mutate(&mut rng, 20);
+                }
+                let json_before = tree.to_json();
+                let expected = tree.is_bst();
+
+                assert_eq!(tree.is_bst_morris(), expected);
+                assert_eq!(tree.to_json(), json_before);
+            }
+        }
     }
 
     #[test]
-    fn test_max_heap_with_right_subtree_unbalanced() {
-        let mut tree = Tree::with_root(10);
-        let last_id = tree.add_node(0, 10, false);
-        tree.add_node(last_id, 5, false);
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+    fn test_morris_long_chains() {
+        use test_support::{chain, CHAIN_LEN};
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_max_heap_with_right_subtree_unbalanced:");
+        for is_left in [true, false] {
+            let mut tree = chain(is_left, |i| if is_left { CHAIN_LEN - i } else { i });
+
+            let mut count = 0;
+            tree.morris_inorder(|_, _| count += 1);
+            assert_eq!(count, CHAIN_LEN);
+            assert!(tree.is_bst_morris());
         }
+    }
+}
 
-        assert_eq!(is_max_heap, false);
-        assert_eq!(max_value, 10);
+
+
+
+
+
+
+
+
+/// Box-based trees (`TreeNode`, with `i32` values), shared with `04_trees.rs`
+mod tree_node;
+
+/// # Box-based trees and a common `BinaryTree` interface
+///
+/// `tree_node.rs` stores every child in an `Option<Box<TreeNode>>`, while `trees::Tree` stores the nodes in a vector
+/// and links them by id. The conversions between the two are lossless for `Tree<i32>`: the keys and the shape are kept,
+/// and a `Tree` built from a `TreeNode` has its ids in preorder (like after `compact()`).
+///
+/// `BinaryTree` exposes the root, the children and the key of the nodes of either representation, so that the functions
+/// of this module (`tree_size`, `is_bst`, `is_balanced`, `to_dot`) are written once for both.
+mod binary_tree {
+
+    use crate::tree_node::TreeNode;
+    use crate::trees;
+    use crate::visualizer;
+    use core::cmp::max;
+    use std::fmt::Display;
+
+    pub trait BinaryTree {
+        type Key;
+        /// a handle to a node: the id for `trees::Tree`, a reference for `TreeNode`
+        type Node<'a>: Copy
+        where
+            Self: 'a;
+
+        /// `None` iff the tree is empty
+        fn root(&self) -> Option<Self::Node<'_>>;
+        /// the id of the node, for the representations that have one (`None` by default)
+        fn node_id<'a>(&'a self, _node: Self::Node<'a>) -> Option<usize> {
+            None
+        }
+        fn left<'a>(&'a self, node: Self::Node<'a>) -> Option<Self::Node<'a>>;
+        fn right<'a>(&'a self, node: Self::Node<'a>) -> Option<Self::Node<'a>>;
+        fn key<'a>(&'a self, node: Self::Node<'a>) -> &'a Self::Key;
     }
 
+    impl<K> BinaryTree for trees::Tree<K> {
+        type Key = K;
+        type Node<'a> = usize where K: 'a;
 
-    #[test]
-    fn test_max_heap_small() {
-        let mut tree = Tree::with_root(9);
+        fn root(&self) -> Option<usize> {
+            self.get_node(0).map(|_| 0)
+        }
 
-        tree.add_node(0, 5, true);
-        tree.add_node(0, 9, false);
+        fn node_id(&self, node: usize) -> Option<usize> {
+            Some(node)
+        }
 
-        tree.add_node(1, 4, false);
+        fn left(&self, node: usize) -> Option<usize> {
+            self.nodes[node].id_left
+        }
 
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+        fn right(&self, node: usize) -> Option<usize> {
+            self.nodes[node].id_right
+        }
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside test_max_heap_small:");
+        fn key(&self, node: usize) -> &K {
+            &self.nodes[node].key
         }
+    }
 
-        assert_eq!(is_max_heap, true);
-        assert_eq!(max_value, 9);
+    impl BinaryTree for TreeNode {
+        type Key = i32;
+        type Node<'a> = &'a TreeNode;
+
+        fn root(&self) -> Option<&TreeNode> {
+            Some(self)
+        }
+
+        fn left<'a>(&'a self, node: &'a TreeNode) -> Option<&'a TreeNode> {
+            node.left.as_deref()
+        }
+
+        fn right<'a>(&'a self, node: &'a TreeNode) -> Option<&'a TreeNode> {
+            node.right.as_deref()
+        }
+
+        fn key<'a>(&'a self, node: &'a TreeNode) -> &'a i32 {
+            &node.val
+        }
     }
 
+    /// The tree used by `tree_size` in `tree_node.rs`, which may be empty
+    impl BinaryTree for Option<Box<TreeNode>> {
+        type Key = i32;
+        type Node<'a> = &'a TreeNode;
 
-    /// generated using https://visualgo.net/en/heap
-    #[test]
-    fn a_random_heap() {
-        let mut tree = Tree::with_root(76);
+        fn root(&self) -> Option<&TreeNode> {
+            self.as_deref()
+        }
 
-        tree.add_node(0, 67, true); // id 1
-        tree.add_node(0, 36, false); // id 2
+        fn left<'a>(&'a self, node: &'a TreeNode) -> Option<&'a TreeNode> {
+            node.left.as_deref()
+        }
 
-        tree.add_node(1, 62, true); // id 3
-        tree.add_node(1, 62, false); // id 4
-        tree.add_node(2, 32, true); // id 5
-        tree.add_node(2, 15, false); // id 6
+        fn right<'a>(&'a self, node: &'a TreeNode) -> Option<&'a TreeNode> {
+            node.right.as_deref()
+        }
 
-        tree.add_node(3, 5, false); // id 7
-        tree.add_node(3, 15, true); // id 8
-        tree.add_node(4, 57, true); // id 9
+        fn key<'a>(&'a self, node: &'a TreeNode) -> &'a i32 {
+            &node.val
+        }
+    }
 
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+    /// A step of `fold_postorder`
+    enum Step<N> {
+        /// fold the subtree rooted at the node (`None` for a missing child)
+        Visit(Option<N>),
+        /// the results of both children of the node are on top of the results stack
+        Combine(N),
+    }
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside a_random_heap:");
+    /// Folds the tree bottom-up, with an explicit stack instead of recursion (so a deep tree does not overflow the stack):
+    /// `combine(node, left, right)` gets the results of the children of `node`, `empty` for a missing child.
+    ///
+    /// # Returns
+    /// the result of the root (`empty` for an empty tree)
+    fn fold_postorder<'a, T, R, F>(tree: &'a T, empty: R, mut combine: F) -> R
+    where
+        T: BinaryTree,
+        R: Clone,
+        F: FnMut(T::Node<'a>, R, R) -> R,
+    {
+        let mut steps = vec![Step::Visit(tree.root())];
+        let mut results: Vec<R> = Vec::new();
+
+        while let Some(step) = steps.pop() {
+            match step {
+                Step::Visit(None) => results.push(empty.clone()),
+                Step::Visit(Some(node)) => {
+                    // the left subtree is folded first, so its result is below the one of the right subtree
+                    steps.push(Step::Combine(node));
+                    steps.push(Step::Visit(tree.right(node)));
+                    steps.push(Step::Visit(tree.left(node)));
+                }
+                Step::Combine(node) => {
+                    let right = results.pop().unwrap();
+                    let left = results.pop().unwrap();
+                    results.push(combine(node, left, right));
+                }
+            }
         }
 
-        assert_eq!(is_max_heap, true);
-        assert_eq!(max_value, 76);
+        results.pop().unwrap()
     }
 
+    /// # Returns
+    /// the number of nodes of the tree
+    pub fn tree_size<T: BinaryTree>(tree: &T) -> usize {
+        fold_postorder(tree, 0, |_, size_left, size_right| 1 + size_left + size_right)
+    }
 
-    /// same as a_random_heap, but with a changed value
-    #[test]
-    fn a_random_non_heap() {
-        let mut tree = Tree::with_root(76);
+    /// # Returns
+    /// true iff the tree is a binary search tree (equal keys are allowed on both sides), as `trees::Tree::is_bst`
+    pub fn is_bst<T: BinaryTree>(tree: &T) -> bool
+    where
+        T::Key: Ord,
+    {
+        // for every subtree: (is_bst, min, max), where `min` and `max` are `None` for an empty subtree
+        let (is_bst, _, _) = fold_postorder(tree, (true, None, None), |node, left, right| {
+            let key = tree.key(node);
+            let (is_left_bst, min_left, max_left) = left;
+            let (is_right_bst, min_right, max_right) = right;
+
+            let am_i_bst = is_left_bst
+                && is_right_bst
+                && max_left.is_none_or(|max_left| max_left <= key)
+                && min_right.is_none_or(|min_right| key <= min_right);
+
+            (am_i_bst, Some(min_left.unwrap_or(key)), Some(max_right.unwrap_or(key)))
+        });
+        is_bst
+    }
 
-        tree.add_node(0, 67, true); // id 1
-        tree.add_node(0, 36, false); // id 2
+    /// # Returns
+    /// true iff, for every node, the heights of its subtrees differ by at most one, as `trees::Tree::is_balanced`
+    pub fn is_balanced<T: BinaryTree>(tree: &T) -> bool {
+        // for every subtree: (is_balanced, height)
+        let (is_balanced, _) = fold_postorder(tree, (true, 0u32), |_, left, right| {
+            let (is_balanced_left, height_left) = left;
+            let (is_balanced_right, height_right) = right;
+
+            let am_i_balanced = is_balanced_left && is_balanced_right && height_left.abs_diff(height_right) <= 1;
+            (am_i_balanced, max(height_left, height_right) + 1)
+        });
+        is_balanced
+    }
 
-        tree.add_node(1, 62, true); // id 3
-        tree.add_node(1, 62, false); // id 4
-        tree.add_node(2, 32, true); // id 5
-        tree.add_node(2, 15, false); // id 6
+    /// # Returns
+    /// the DOT representation of the tree, as `trees::Tree::to_dot`.
+    /// The nodes of a `TreeNode` are numbered in preorder, since they have no id.
+    pub fn to_dot<T: BinaryTree>(tree: &T) -> String
+    where
+        T::Key: Display,
+    {
+        visualizer::write_dot(tree, &visualizer::DotOptions::new())
+    }
 
-        tree.add_node(3, 5, false); // id 7
-        tree.add_node(3, 64, true); // id 8
-        tree.add_node(4, 57, true); // id 9
+    /// # Returns
+    /// the URL to visualize the tree in a web browser (used by `trees::Tree::get_visualization_url`)
+    pub fn get_visualization_url<T: BinaryTree>(tree: &T) -> String
+    where
+        T::Key: Display,
+    {
+        format!("https://edotor.net/?engine=dot#{}", urlencoding::encode(&to_dot(tree)))
+    }
 
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+    impl From<&TreeNode> for trees::Tree<i32> {
+        /// Copies the tree, numbering the nodes in preorder. The `TreeNode` is only borrowed.
+        fn from(root: &TreeNode) -> Self {
+            let mut tree = trees::Tree::with_root(root.val);
+
+            // the nodes are added in preorder: (node, its parent id and whether it is the left child, `None` for the root)
+            let mut stack: Vec<(&TreeNode, Option<(usize, bool)>)> = vec![(root, None)];
+            while let Some((node, parent)) = stack.pop() {
+                let id = match parent {
+                    None => 0,
+                    Some((parent_id, is_left)) => tree.add_node(parent_id, node.val, is_left),
+                };
+
+                // the right child is pushed first, so that the left one is added first
+                if let Some(right) = node.right.as_deref() {
+                    stack.push((right, Some((id, false))));
+                }
+                if let Some(left) = node.left.as_deref() {
+                    stack.push((left, Some((id, true))));
+                }
+            }
+            tree
+        }
+    }
+
+    impl From<&trees::Tree<i32>> for Box<TreeNode> {
+        /// Copies the tree, building the nodes bottom-up (children before parents), without recursion
+        fn from(tree: &trees::Tree<i32>) -> Self {
+            let mut boxes: Vec<Option<Box<TreeNode>>> = (0..tree.nodes.len()).map(|_| None).collect();
+
+            for (id, node) in tree.postorder() {
+                let left = node.id_left.and_then(|child_id| boxes[child_id].take());
+                let right = node.id_right.and_then(|child_id| boxes[child_id].take());
+                boxes[id] = Some(Box::new(TreeNode { val: node.key, left, right }));
+            }
+
+            boxes[0].take().expect("a tree always has a root")
+        }
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod binary_tree_tests {
+    use super::*;
+    use tree_node::TreeNode;
+    use random_trees::TreeRng;
+    use trees::*;
+
+    fn leaf(val: i32) -> Option<Box<TreeNode>> {
+        Some(Box::new(TreeNode { val, left: None, right: None }))
+    }
+
+    /// ```text
+    ///        10
+    ///       /  \
+    ///      5    20
+    ///       \
+    ///        -7
+    /// ```
+    fn build_box_tree() -> TreeNode {
+        TreeNode {
+            val: 10,
+            left: Some(Box::new(TreeNode { val: 5, left: None, right: leaf(-7) })),
+            right: leaf(20),
+        }
+    }
+
+    #[test]
+    fn test_box_to_arena() {
+        let root = build_box_tree();
+        let tree = Tree::from(&root);
 
         if PRINT_TREE {
-            tree.print_visualization_url("Tree inside a_random_non_heap:");
+            tree.print_visualization_url("Tree inside test_box_to_arena:");
         }
 
-        assert_eq!(is_max_heap, false);
-        assert_eq!(max_value, 76);
+        // the ids are in preorder
+        assert_eq!(tree.to_level_order(), "[10,5,20,null,-7]");
+        assert_eq!(tree.get_node(2).unwrap().key, -7);
+        assert_eq!(tree.get_node(3).unwrap().key, 20);
+
+        // the Box tree is only borrowed
+        assert_eq!(root.val, 10);
     }
 
     #[test]
-    fn another_random_non_heap() {
-        let mut tree = Tree::with_root(76);
+    fn test_arena_to_box() {
+        let mut tree: Tree<i32> = Tree::from_level_order("[10,5,20,null,-7]").unwrap();
+        tree.remove_subtree(2);
 
-        tree.add_node(0, 67, true); // id 1
-        tree.add_node(0, 36, false); // id 2
+        let root: Box<TreeNode> = Box::from(&tree);
+        assert_eq!(root.val, 10);
+        assert!(root.right.is_none());
+        assert_eq!(root.left.as_ref().unwrap().right.as_ref().unwrap().val, -7);
 
-        tree.add_node(1, 62, true); // id 3
-        tree.add_node(1, 62, false); // id 4
-        tree.add_node(2, 37, true); // id 5
-        tree.add_node(2, 15, false); // id 6
+        // free slots are not copied, so the ids of the tree built back are in preorder
+        assert_eq!(Tree::from(root.as_ref()).to_level_order(), "[10,5,null,null,-7]");
+    }
 
-        tree.add_node(3, 5, false); // id 7
-        tree.add_node(3, 15, true); // id 8
-        tree.add_node(4, 57, true); // id 9
+    #[test]
+    fn test_round_trips() {
+        let mut rng = TreeRng::new(24);
 
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
+        for _ in 0..200 {
+            let size = 1 + rng.below(50);
+            let tree: Tree<i32> = Tree::from_level_order(&Tree::random_tree(&mut rng, size, 100).to_level_order()).unwrap();
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside another_random_non_heap:");
+            let root: Box<TreeNode> = Box::from(&tree);
+            let back = Tree::from(root.as_ref());
+            assert_eq!(back.to_level_order(), tree.to_level_order());
+
+            // Box -> Tree -> Box keeps the ids in preorder, so the arena is the same
+            let again: Box<TreeNode> = Box::from(&back);
+            assert_eq!(Tree::from(again.as_ref()).to_json(), back.to_json());
         }
+    }
 
-        assert_eq!(is_max_heap, false);
-        assert_eq!(max_value, 76);
+    #[test]
+    fn test_algorithms_on_both_representations() {
+        let mut rng = TreeRng::new(42);
+
+        for _ in 0..500 {
+            let size = 1 + rng.below(30);
+            let mut tree: Tree<i32> = Tree::from_level_order(&Tree::random_bst(&mut rng, size, 50).to_level_order()).unwrap();
+            if rng.below(2) == 0 {
+                tree.nodes[rng.below(size)].key = rng.key(50) as i32;
+            }
+            let root: Box<TreeNode> = Box::from(&tree);
+
+            assert_eq!(binary_tree::tree_size(&tree), tree.node_count());
+            assert_eq!(binary_tree::tree_size(root.as_ref()), tree.node_count());
+
+            assert_eq!(binary_tree::is_bst(&tree), tree.is_bst());
+            assert_eq!(binary_tree::is_bst(root.as_ref()), tree.is_bst());
+
+            assert_eq!(binary_tree::is_balanced(&tree), tree.is_balanced());
+            assert_eq!(binary_tree::is_balanced(root.as_ref()), tree.is_balanced());
+        }
     }
 
     #[test]
-    fn non_heap_final() {
-        let mut tree = Tree::with_root(76);
+    fn test_optional_box_tree() {
+        let empty: Option<Box<TreeNode>> = None;
+        assert_eq!(binary_tree::tree_size(&empty), 0);
+        assert!(binary_tree::is_bst(&empty));
+        assert!(binary_tree::is_balanced(&empty));
+        assert_eq!(binary_tree::to_dot(&empty), "digraph Tree {\n}\n");
+
+        let root = Some(Box::new(build_box_tree()));
+        assert_eq!(binary_tree::tree_size(&root), 4);
+        assert_eq!(binary_tree::tree_size(&root) as i32, tree_node::tree_size(Some(&build_box_tree())));
+    }
 
-        tree.add_node(0, 67, true); // id 1
-        tree.add_node(0, 36, false); // id 2
+    #[test]
+    fn test_to_dot() {
+        // the nodes of a Tree with the ids in preorder are numbered in the same way
+        let root = build_box_tree();
+        let tree = Tree::from(&root);
+
+        assert_eq!(binary_tree::to_dot(&root), tree.to_dot());
+        assert_eq!(binary_tree::to_dot(&tree), tree.to_dot());
+        assert_eq!(binary_tree::get_visualization_url(&root), tree.get_visualization_url());
+
+        // a Tree keeps its own ids, even when they are not in preorder
+        let tree: Tree<i32> = Tree::from_level_order("[10,5,20,null,-7]").unwrap();
+        assert_eq!(binary_tree::to_dot(&tree), tree.to_dot());
+        assert!(tree.to_dot().contains("  id0 -> id2;\n"));
+        assert!(binary_tree::to_dot(&root).contains("  id0 -> id3;\n"));
+
+        // the options apply to both representations
+        let options = visualizer::DotOptions::new().highlight([1]).same_rank_per_level(true);
+        assert_eq!(visualizer::write_dot(&root, &options), Tree::from(&root).to_dot_with(&options));
+    }
 
-        tree.add_node(1, 62, true); // id 3
-        tree.add_node(1, 62, false); // id 4
-        tree.add_node(2, 32, true); // id 5
-        tree.add_node(2, 15, false); // id 6
+    #[test]
+    fn test_deep_chain() {
+        use test_support::{chain, CHAIN_LEN};
+
+        // every node is the left child of the previous one, and the keys decrease going down
+        let tree = chain(true, |i| (CHAIN_LEN - i) as i32);
+        // the conversion does not recurse either
+        let root: Option<Box<TreeNode>> = Some(Box::from(&tree));
+
+        // none of the functions recurses on the depth of the tree
+        assert_eq!(binary_tree::tree_size(&tree), CHAIN_LEN as usize);
+        assert_eq!(binary_tree::tree_size(&root), CHAIN_LEN as usize);
+        assert!(binary_tree::is_bst(&tree));
+        assert!(binary_tree::is_bst(&root));
+        assert!(!binary_tree::is_balanced(&tree));
+        assert!(!binary_tree::is_balanced(&root));
+        assert_eq!(binary_tree::to_dot(&tree).matches(" -> ").count(), CHAIN_LEN as usize - 1);
+        assert_eq!(binary_tree::to_dot(&root), binary_tree::to_dot(&tree));
+    }
+}
 
-        tree.add_node(3, 5, false); // id 7
-        tree.add_node(3, 15, true); // id 8
 
-        tree.add_node(5, 1, true); // id 9
 
-        let (is_max_heap, max_value) = tree.is_max_heap_with_max();
 
-        if PRINT_TREE {
-            tree.print_visualization_url("Tree inside non_heap_final:");
+
+
+
+
+
+/// # Helpers shared by the tests
+#[cfg(test)]
+mod test_support {
+
+    use crate::trees;
+    use crate::trees::Tree;
+    use std::fmt::Display;
+
+    /// The generator of the randomized tests is the one of the random tree generators
+    pub use crate::random_trees::TreeRng;
+
+    /// A perfect tree with 3 levels, with the ids in level order and every key equal to its id plus one:
+    /// ```text
+    ///            1 (0)
+    ///          /       \
+    ///       2 (1)       3 (2)
+    ///       /   \       /   \
+    ///    4 (3) 5 (4)  6 (5) 7 (6)
+    /// ```
+    pub fn perfect_tree() -> Tree {
+        Tree::from_level_order("[1,2,3,4,5,6,7]").unwrap()
+    }
+
+    /// A tree whose leaves are at different depths, with the ids in level order and every key equal to its id plus one:
+    /// ```text
+    ///            1 (0)
+    ///          /       \
+    ///       2 (1)       3 (2)
+    ///      /    \           \
+    ///   4 (3)   5 (4)       6 (5)
+    ///          /
+    ///       7 (6)
+    /// ```
+    pub fn sample_tree() -> Tree {
+        Tree::from_level_order("[1,2,3,4,5,null,6,null,null,7]").unwrap()
+    }
+
+    /// The shape of `sample_tree`, with signed keys:
+    /// ```text
+    ///              10 (0)
+    ///            /        \
+    ///        -2 (1)        7 (2)
+    ///        /    \          \
+    ///     8 (3)  -4 (4)      -1 (5)
+    ///              /
+    ///           20 (6)
+    /// ```
+    pub fn signed_tree() -> Tree<i32> {
+        Tree::from_level_order("[10,-2,7,8,-4,null,-1,null,null,20]").unwrap()
+    }
+
+    /// A tree that is not a BST, while its left subtree is one (and so are the leaves):
+    /// ```text
+    ///             10 (0)
+    ///           /       \
+    ///        5 (1)      15 (2)
+    ///        /   \         \
+    ///     1 (3)  8 (4)     7 (5)
+    /// ```
+    pub fn almost_bst() -> Tree {
+        Tree::from_level_order("[10,5,15,1,8,null,7]").unwrap()
+    }
+
+    /// The number of nodes of the path-shaped trees of the stack-safety tests, deep enough to overflow the stack of a recursive function
+    pub const CHAIN_LEN: u32 = 1_000_000;
+
+    /// Builds a path-shaped tree with `CHAIN_LEN` nodes, where every node is the left (or right) child of the previous one.
+    /// The ids follow the depth: the node `i` has the key `key_of(i)`, and its subtree is a chain of `CHAIN_LEN - i` nodes.
+    pub fn chain<K>(is_left: bool, key_of: impl Fn(u32) -> K) -> Tree<K> {
+        let mut tree = Tree::with_root(key_of(0));
+        let mut last_id = 0;
+        for i in 1..CHAIN_LEN {
+            last_id = tree.add_node(last_id, key_of(i), is_left);
         }
+        tree
+    }
 
-        assert_eq!(is_max_heap, false);
-        assert_eq!(max_value, 76);
+    /// Builds a BST by inserting `keys` in order with `bst_insert`, starting from a root with `keys[0]`
+    pub fn build_bst(keys: &[u32]) -> Tree {
+        let mut tree = Tree::with_root(keys[0]);
+        for key in &keys[1..] {
+            tree.bst_insert(*key);
+        }
+        tree
+    }
+
+    /// # Returns
+    /// the keys of the tree in order (sorted, if the tree is a BST)
+    pub fn inorder_keys<K: Clone>(tree: &Tree<K>) -> Vec<K> {
+        tree.inorder().map(|(_, node)| node.key.clone()).collect()
+    }
+
+    /// Panics if `condition` is false, like `assert!`. When `PRINT_TREE` is set, the SVG image of `tree` is written
+    /// first (see `dump_svg`), named after the check, and its path is printed.
+    #[track_caller]
+    pub fn assert_tree<K: Display>(tree: &trees::Tree<K>, condition: bool, check: &str) {
+        if condition {
+            return;
+        }
+        if crate::PRINT_TREE {
+            match tree.dump_svg(check) {
+                Ok(path) => eprintln!("the tree that failed the check {} is in {}", check, path.display()),
+                Err(error) => eprintln!("the tree that failed the check {} cannot be written: {}", check, error),
+            }
+        }
+        panic!("the check {} failed", check);
     }
 
+    /// The oracle of the randomized tests of the search trees: the keys (duplicates included) in a sorted vector
+    pub struct SortedKeys {
+        pub keys: Vec<u32>,
+    }
 
+    impl SortedKeys {
+        pub fn new(keys: &[u32]) -> Self {
+            let mut keys = keys.to_vec();
+            keys.sort_unstable();
+            Self { keys }
+        }
 
+        /// Inserts `key` after its duplicates, as `bst_insert` does
+        pub fn insert(&mut self, key: u32) {
+            let position = self.keys.partition_point(|k| *k <= key);
+            self.keys.insert(position, key);
+        }
 
-}
\ No newline at end of file
+        /// Removes one occurrence of `key`
+        ///
+        /// # Returns
+        /// true iff `key` was present
+        pub fn remove(&mut self, key: &u32) -> bool {
+            match self.keys.iter().position(|k| k == key) {
+                Some(position) => {
+                    self.keys.remove(position);
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+}
@@ -14,237 +14,7 @@ fn main() {
 
 
 /// EXERCISE 1
-pub mod ex_1_segment {
-
-
-
-    #[derive(Clone)]
-    struct SegmentTreeNode {
-        start: usize, // start of the subarray covered by the node
-        end: usize, 
-        max_value: i32, // maximum value in the coverage of the node
-    }
-
-
-    pub struct SegmentTree {
-        tree: Vec<SegmentTreeNode>,
-    }
-
-
-
-    // Constructor for the Segment Tree
-    impl SegmentTree {
-        pub fn new(size: usize) -> Self {
-            // Allocate space for nodes
-            let tree_size = 2 * (size.next_power_of_two()) - 1;
-            let tree = vec![SegmentTreeNode { start: usize::MAX, end: usize::MAX, max_value:  std::i32::MIN};    tree_size];
-            Self { tree }
-        }
-    }
-
-
-
-
-    // initialization (building the tree from the array)
-    impl SegmentTree {
-
-
-
-        /// Public function to initialize the segment tree with the given array
-        pub fn initialize(&mut self, arr: &Vec<i32>) {
-            let n = arr.len();
-            self.build_tree(arr, 0, 0, n - 1); // building a tree that covers the whole array
-        }
-
-
-        /// Recursive helper function to build the segment tree
-        /// # Arguments:
-        /// - arr: the original array
-        /// - node: the index of the node (in the array) to be built
-        /// - start: the start index of the subarray covered by the node
-        /// - end: the end index of the subarray covered by the node
-        fn build_tree(&mut self, arr: &Vec<i32>, node: usize, start: usize, end: usize) {
-            
-            // Set the node's start and end values
-            self.tree[node].start = start;
-            self.tree[node].end = end;
-    
-
-            if start == end { 
-                // Leaf node. the value is simply the array element
-                self.tree[node].max_value = arr[start];
-
-            } else {
-                // Non-leaf node. recursively build its children
-
-                let mid = (start + end) / 2; // midpoint of the subarray
-
-                let left_child = 2 * node + 1; // index of the node in the tree vector
-                let right_child = 2 * node + 2; // index of the node in the tree vector
-    
-                self.build_tree(arr, left_child, start, mid);
-                self.build_tree(arr, right_child, mid + 1, end);
-    
-
-                // Combine information from children (in this case, the max value)
-                let left_child_max = self.tree[left_child].max_value;
-                let right_child_max = self.tree[right_child].max_value;
-
-                let max_value = std::cmp::max(left_child_max, right_child_max);
-                self.tree[node].max_value = max_value;
-            }
-        }
-    
-
-    }
-    
-
-
-
-
-    
-    // queries and update
-    impl SegmentTree {
-
-
-        /// Public function to get the maximum value in a range
-        pub fn query(&self, query_start: usize, query_end: usize) -> i32 {
-            // I substract 1 because, for the queries, the array starts from 1
-            // but in the actual implementation, the array starts with 0
-            self.query_helper(0, query_start-1, query_end-1)
-        }
-
-
-
-        /// Recursive function to query the segment tree for the maximum value in a range
-        fn query_helper(&self, node: usize, query_start: usize, query_end: usize) -> i32 {
-            // println!("query({}, {}, {})", node, query_start, query_end);
-            
-            // Case 1: No overlap
-            if query_end < self.tree[node].start || query_start > self.tree[node].end {
-                return std::i32::MIN;
-            }
-    
-
-            // Case 2: Complete overlap
-            if query_start <= self.tree[node].start && query_end >= self.tree[node].end {
-                return self.tree[node].max_value;
-            }
-    
-
-            // Case 3: Partial overlap, recursively go both left and right
-            let left_child = 2 * node + 1;
-            let right_child = 2 * node + 2;
-    
-            let mut left_result = std::i32::MIN;
-            let mut right_result = std::i32::MIN;
-            let mid = (self.tree[node].start + self.tree[node].end) / 2;
-
-            if query_start <= mid {
-                left_result = self.query_helper(left_child, query_start, query_end);
-            }
-            if query_end > mid {
-                right_result = self.query_helper(right_child, query_start, query_end);
-            }
-    
-            // Combine results from children (e.g., max value)
-            return std::cmp::max(left_result, right_result);
-        }
-    
-
-        
-
-
-
-
-        /// Public function to perform an update
-        /// # Arguments
-        /// - start: the start index of the range to be updated
-        /// - end: the end index of the range to be updated
-        /// - new_value: the new value to be set IFF it is smaller than the current value
-        pub fn update(&mut self, start: usize, end: usize, new_value: i32) {
-            // I substract 1 because, for the queries, the array starts from 1
-            // but in the actual implementation, the array starts with 0
-            self.update_helper(0, start-1, end-1, new_value);
-        }
-
-
-        // Recursive function to update the segment tree
-        fn update_helper(&mut self, node: usize, query_start: usize, query_end: usize, new_value: i32) {
-            // base case: out of range
-            if node > self.tree.len() - 1 {
-                return;
-            }
-
-            if query_start > self.tree.len() - 1 || query_end > self.tree.len() - 1 {
-                return;
-            }
-
-            if query_end < self.tree[node].start || query_start > self.tree[node].end {
-                return;
-            }
-
-
-            // Base case: I am in a leaf node
-            if 
-                self.tree[node].start == self.tree[node].end
-            {
-                // update the max value, if in the range of the query
-                if query_start <= self.tree[node].start && query_end >= self.tree[node].end {
-                    let new_min = std::cmp::min(self.tree[node].max_value, new_value);
-                    self.tree[node].max_value = new_min;
-                }
-                return;
-            }
-
-
-            // Non-base case: I am in a non-leaf node, update children accordingly
-            let left_child = 2 * node + 1;
-            let right_child = 2 * node + 2;
-    
-            let mid = (self.tree[node].start + self.tree[node].end) / 2;
-            
-            // now we update the children. We only update the ones that overlap with the query.
-            if query_start <= mid {
-                self.update_helper(left_child, query_start, query_end, new_value);
-            }
-            if query_end > mid {
-                self.update_helper(right_child, query_start, query_end, new_value);
-            }
-    
-
-            // Update the current node based on children
-            let left_max = self.tree[left_child].max_value;
-            let right_max = self.tree[right_child].max_value;
-            let new_max = std::cmp::max(left_max, right_max);
-            self.tree[node].max_value = new_max;
-        }
-    
-
-    }
-
-
-
-
-    impl SegmentTree {
-
-        pub fn to_string(&self) -> String {
-            let mut result = String::new();
-            for i in 0..self.tree.len() {
-                result.push_str("------------\n");
-                result.push_str(&format!("node_index: {}\n", i));
-                result.push_str(&format!("start: {}\n", self.tree[i].start));
-                result.push_str(&format!("end: {}\n", self.tree[i].end));
-                result.push_str(&format!("max_value: {}\n", self.tree[i].max_value));
-                result.push_str("------------\n");
-            }
-            result
-        }
-        
-    }
-    
-
-}
+pub mod ex_1_segment;
 
 
 
@@ -365,83 +135,44 @@ mod tests {
             println!("Test files number {} passed!", i); 
         }
     }
-}
 
 
 
+    #[test]
+    fn test_segment_tree_add() {
+        let mut array: Vec<i64> = vec![5, -3, 8, 0, 2, 7, -1];
+        let mut segment_tree = ex_1_segment::SegmentTree::new(array.len());
+        segment_tree.initialize(&array);
+
+        // (kind, start, end, value): 0 is a chmin update, 1 an add (the indexes start from 1)
+        let updates: [(u8, usize, usize, i64); 6] = [(1, 2, 5, 10), (0, 1, 4, 6), (1, 4, 7, -4), (1, 1, 7, 3), (0, 3, 6, 4), (1, 3, 3, 20)];
+
+        for (kind, start, end, value) in updates {
+            if kind == 0 {
+                segment_tree.update(start, end, value);
+                array[start - 1..end].iter_mut().for_each(|v| *v = (*v).min(value));
+            } else {
+                segment_tree.add(start, end, value);
+                array[start - 1..end].iter_mut().for_each(|v| *v += value);
+            }
 
+            for query_start in 1..=array.len() {
+                for query_end in query_start..=array.len() {
+                    let expected = *array[query_start - 1..query_end].iter().max().unwrap();
+                    assert_eq!(segment_tree.query(query_start, query_end), expected, "query({}, {}) on {:?}", query_start, query_end, array);
+                }
+            }
+        }
+    }
+}
 
 
 
-/// # Author
-/// https://pages.di.unipi.it/rossano/blog/2023/fenwick/
-pub mod fenwick {
-    #[derive(Debug)]
-    pub struct FenwickTree {
-        tree: Vec<i64>,
-    }
-    
-    impl FenwickTree {
-        pub fn with_len(n: usize) -> Self {
-            Self {
-                tree: vec![0; n + 1],
-            }
-        }
-    
-        pub fn len(&self) -> usize {
-            self.tree.len() - 1
-        }
-    
-        /// Indexing is 0-based, even if internally we use 1-based indexing
-        pub fn add(&mut self, i: usize, delta: i64) {
-            let mut i = i + 1; 
-            assert!(i < self.tree.len(), "Index out of bounds. Tried to add {} to index {}, but tree length is {}", delta, i, self.tree.len());
-    
-            while i < self.tree.len() {
-                self.tree[i] += delta;
-                i = Self::next_sibling(i);
-            }
-        }
-    
-        /// Indexing is 0-based, even if internally we use 1-based indexing
-        pub fn sum(&self, i: usize) -> i64 {
-            let mut i = i + 1;  
-    
-            assert!(i < self.tree.len());
-            let mut sum = 0;
-            while i != 0 {
-                sum += self.tree[i];
-                i = Self::parent(i);
-            }
-    
-            sum
-        }
-    
-        pub fn range_sum(&self, l: usize, r: usize) -> i64 {
-            self.sum(r) - if l == 0 { 0 } else { self.sum(l - 1) }
-        }
-    
-        fn isolate_trailing_one(i: usize) -> usize {
-            if i == 0 {
-                0
-            } else {
-                1 << i.trailing_zeros()
-            }
-        }
-    
-        fn parent(i: usize) -> usize {
-            i - Self::isolate_trailing_one(i)
-        }
-    
-        fn next_sibling(i: usize) -> usize {
-            i + Self::isolate_trailing_one(i)
-        }
 
 
 
 
-    }
-}
+pub mod fenwick;
 
 
 
@@ -0,0 +1,330 @@
+//! Segment tree of exercise 1 of the segment trees hands-on: range maximum queries,
+//! range updates that replace every value `v` in the range with `min(v, new_value)`,
+//! and range updates that add the same amount to every value in the range.
+//! It is shared by the binaries that declare `mod ex_1_segment;`.
+
+use std::fmt;
+use std::ops::Add;
+
+/// The values stored in a `SegmentTree`
+pub trait SegmentValue: Copy + Ord + Add<Output = Self> + fmt::Display {
+    /// the maximum of an empty range
+    const MIN: Self;
+    /// the amount that leaves a value unchanged when it is added
+    const ZERO: Self;
+}
+
+impl SegmentValue for i32 {
+    const MIN: Self = i32::MIN;
+    const ZERO: Self = 0;
+}
+
+impl SegmentValue for i64 {
+    const MIN: Self = i64::MIN;
+    const ZERO: Self = 0;
+}
+
+#[derive(Clone)]
+struct SegmentTreeNode<T> {
+    start: usize, // start of the subarray covered by the node
+    end: usize,
+    max_value: T, // maximum value in the coverage of the node
+    pending_add: T, // amount added to the whole coverage of the node, not yet added to its children
+}
+
+
+pub struct SegmentTree<T = i32> {
+    tree: Vec<SegmentTreeNode<T>>,
+}
+
+
+
+// Constructor for the Segment Tree
+impl<T: SegmentValue> SegmentTree<T> {
+    pub fn new(size: usize) -> Self {
+        // Allocate space for nodes
+        let tree_size = 2 * (size.next_power_of_two()) - 1;
+        let tree = vec![SegmentTreeNode { start: usize::MAX, end: usize::MAX, max_value: T::MIN, pending_add: T::ZERO };    tree_size];
+        Self { tree }
+    }
+}
+
+
+
+
+// initialization (building the tree from the array)
+impl<T: SegmentValue> SegmentTree<T> {
+
+
+
+    /// Public function to initialize the segment tree with the given array
+    pub fn initialize(&mut self, arr: &[T]) {
+        let n = arr.len();
+        self.build_tree(arr, 0, 0, n - 1); // building a tree that covers the whole array
+    }
+
+
+    /// Recursive helper function to build the segment tree
+    /// # Arguments:
+    /// - arr: the original array
+    /// - node: the index of the node (in the array) to be built
+    /// - start: the start index of the subarray covered by the node
+    /// - end: the end index of the subarray covered by the node
+    fn build_tree(&mut self, arr: &[T], node: usize, start: usize, end: usize) {
+
+        // Set the node's start and end values
+        self.tree[node].start = start;
+        self.tree[node].end = end;
+        self.tree[node].pending_add = T::ZERO;
+
+
+        if start == end {
+            // Leaf node. the value is simply the array element
+            self.tree[node].max_value = arr[start];
+
+        } else {
+            // Non-leaf node. recursively build its children
+
+            let mid = (start + end) / 2; // midpoint of the subarray
+
+            let left_child = 2 * node + 1; // index of the node in the tree vector
+            let right_child = 2 * node + 2; // index of the node in the tree vector
+
+            self.build_tree(arr, left_child, start, mid);
+            self.build_tree(arr, right_child, mid + 1, end);
+
+
+            // Combine information from children (in this case, the max value)
+            let left_child_max = self.tree[left_child].max_value;
+            let right_child_max = self.tree[right_child].max_value;
+
+            let max_value = std::cmp::max(left_child_max, right_child_max);
+            self.tree[node].max_value = max_value;
+        }
+    }
+
+
+}
+
+
+
+
+
+
+// queries and update
+impl<T: SegmentValue> SegmentTree<T> {
+
+
+    /// Public function to get the maximum value in a range
+    pub fn query(&self, query_start: usize, query_end: usize) -> T {
+        // I substract 1 because, for the queries, the array starts from 1
+        // but in the actual implementation, the array starts with 0
+        self.query_helper(0, query_start-1, query_end-1)
+    }
+
+
+
+    /// Recursive function to query the segment tree for the maximum value in a range
+    fn query_helper(&self, node: usize, query_start: usize, query_end: usize) -> T {
+        // println!("query({}, {}, {})", node, query_start, query_end);
+
+        // Case 1: No overlap
+        if query_end < self.tree[node].start || query_start > self.tree[node].end {
+            return T::MIN;
+        }
+
+
+        // Case 2: Complete overlap
+        if query_start <= self.tree[node].start && query_end >= self.tree[node].end {
+            return self.tree[node].max_value;
+        }
+
+
+        // Case 3: Partial overlap, recursively go both left and right
+        let left_child = 2 * node + 1;
+        let right_child = 2 * node + 2;
+
+        let mut left_result = T::MIN;
+        let mut right_result = T::MIN;
+        let mid = (self.tree[node].start + self.tree[node].end) / 2;
+
+        if query_start <= mid {
+            left_result = self.query_helper(left_child, query_start, query_end);
+        }
+        if query_end > mid {
+            right_result = self.query_helper(right_child, query_start, query_end);
+        }
+
+        // Combine results from children (e.g., max value),
+        // adding what was added to this node but not yet to its children
+        return std::cmp::max(left_result, right_result) + self.tree[node].pending_add;
+    }
+
+
+
+
+
+
+
+    /// Public function to perform an update
+    /// # Arguments
+    /// - start: the start index of the range to be updated
+    /// - end: the end index of the range to be updated
+    /// - new_value: the new value to be set IFF it is smaller than the current value
+    pub fn update(&mut self, start: usize, end: usize, new_value: T) {
+        // I substract 1 because, for the queries, the array starts from 1
+        // but in the actual implementation, the array starts with 0
+        self.update_helper(0, start-1, end-1, new_value);
+    }
+
+
+    // Recursive function to update the segment tree
+    fn update_helper(&mut self, node: usize, query_start: usize, query_end: usize, new_value: T) {
+        // base case: out of range
+        if node > self.tree.len() - 1 {
+            return;
+        }
+
+        if query_start > self.tree.len() - 1 || query_end > self.tree.len() - 1 {
+            return;
+        }
+
+        if query_end < self.tree[node].start || query_start > self.tree[node].end {
+            return;
+        }
+
+
+        // Base case: I am in a leaf node
+        if
+            self.tree[node].start == self.tree[node].end
+        {
+            // update the max value, if in the range of the query
+            if query_start <= self.tree[node].start && query_end >= self.tree[node].end {
+                let new_min = std::cmp::min(self.tree[node].max_value, new_value);
+                self.tree[node].max_value = new_min;
+            }
+            return;
+        }
+
+
+        // Non-base case: I am in a non-leaf node, update children accordingly
+        let left_child = 2 * node + 1;
+        let right_child = 2 * node + 2;
+
+        // the children must be up to date before they are compared with new_value
+        self.push_add(node);
+
+        let mid = (self.tree[node].start + self.tree[node].end) / 2;
+
+        // now we update the children. We only update the ones that overlap with the query.
+        if query_start <= mid {
+            self.update_helper(left_child, query_start, query_end, new_value);
+        }
+        if query_end > mid {
+            self.update_helper(right_child, query_start, query_end, new_value);
+        }
+
+
+        // Update the current node based on children
+        let left_max = self.tree[left_child].max_value;
+        let right_max = self.tree[right_child].max_value;
+        let new_max = std::cmp::max(left_max, right_max);
+        self.tree[node].max_value = new_max;
+    }
+
+
+
+
+    /// Public function to add the same amount to every value in a range
+    /// # Arguments
+    /// - start: the start index of the range to be updated
+    /// - end: the end index of the range to be updated
+    /// - delta: the amount added to every value in the range
+    ///
+    /// The update is lazy: a node covered by the range only records `delta`,
+    /// which is added to its children when an update or a query needs them.
+    pub fn add(&mut self, start: usize, end: usize, delta: T) {
+        // same indexing as the queries, starting from 1
+        self.add_helper(0, start-1, end-1, delta);
+    }
+
+
+    // Recursive function to add delta to the range
+    fn add_helper(&mut self, node: usize, query_start: usize, query_end: usize, delta: T) {
+        // base case: out of range
+        if query_end < self.tree[node].start || query_start > self.tree[node].end {
+            return;
+        }
+
+
+        // Complete overlap: the max of the node grows by delta, and so will the values of its children
+        if query_start <= self.tree[node].start && query_end >= self.tree[node].end {
+            self.apply_add(node, delta);
+            return;
+        }
+
+
+        // Partial overlap (so not a leaf): update the children that overlap with the range
+        let left_child = 2 * node + 1;
+        let right_child = 2 * node + 2;
+
+        let mid = (self.tree[node].start + self.tree[node].end) / 2;
+
+        if query_start <= mid {
+            self.add_helper(left_child, query_start, query_end, delta);
+        }
+        if query_end > mid {
+            self.add_helper(right_child, query_start, query_end, delta);
+        }
+
+
+        // Update the current node based on children, and on what they still have to receive
+        let left_max = self.tree[left_child].max_value;
+        let right_max = self.tree[right_child].max_value;
+        self.tree[node].max_value = std::cmp::max(left_max, right_max) + self.tree[node].pending_add;
+    }
+
+
+    /// Adds the pending amount of a (non-leaf) node to its children
+    fn push_add(&mut self, node: usize) {
+        let pending_add = self.tree[node].pending_add;
+        if pending_add == T::ZERO {
+            return;
+        }
+
+        self.apply_add(2 * node + 1, pending_add);
+        self.apply_add(2 * node + 2, pending_add);
+        self.tree[node].pending_add = T::ZERO;
+    }
+
+
+    /// Adds delta to the whole coverage of a node. A leaf has no children, so nothing is left pending.
+    fn apply_add(&mut self, node: usize, delta: T) {
+        self.tree[node].max_value = self.tree[node].max_value + delta;
+        if self.tree[node].start != self.tree[node].end {
+            self.tree[node].pending_add = self.tree[node].pending_add + delta;
+        }
+    }
+
+
+}
+
+
+
+
+impl<T: SegmentValue> fmt::Display for SegmentTree<T> {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, node) in self.tree.iter().enumerate() {
+            writeln!(f, "------------")?;
+            writeln!(f, "node_index: {}", i)?;
+            writeln!(f, "start: {}", node.start)?;
+            writeln!(f, "end: {}", node.end)?;
+            writeln!(f, "max_value: {}", node.max_value)?;
+            writeln!(f, "pending_add: {}", node.pending_add)?;
+            writeln!(f, "------------")?;
+        }
+        Ok(())
+    }
+
+}
@@ -0,0 +1,75 @@
+//! Fenwick tree (binary indexed tree) over `i64` values.
+//! It is shared by the binaries that declare `mod fenwick;`.
+//!
+//! # Author
+//! https://pages.di.unipi.it/rossano/blog/2023/fenwick/
+
+#[derive(Debug)]
+pub struct FenwickTree {
+    tree: Vec<i64>,
+}
+
+impl FenwickTree {
+    pub fn with_len(n: usize) -> Self {
+        Self {
+            tree: vec![0; n + 1],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Indexing is 0-based, even if internally we use 1-based indexing
+    pub fn add(&mut self, i: usize, delta: i64) {
+        let mut i = i + 1; 
+        assert!(i < self.tree.len(), "Index out of bounds. Tried to add {} to index {}, but tree length is {}", delta, i, self.tree.len());
+
+        while i < self.tree.len() {
+            self.tree[i] += delta;
+            i = Self::next_sibling(i);
+        }
+    }
+
+    /// Indexing is 0-based, even if internally we use 1-based indexing
+    pub fn sum(&self, i: usize) -> i64 {
+        let mut i = i + 1;  
+
+        assert!(i < self.tree.len());
+        let mut sum = 0;
+        while i != 0 {
+            sum += self.tree[i];
+            i = Self::parent(i);
+        }
+
+        sum
+    }
+
+    pub fn range_sum(&self, l: usize, r: usize) -> i64 {
+        self.sum(r) - if l == 0 { 0 } else { self.sum(l - 1) }
+    }
+
+    fn isolate_trailing_one(i: usize) -> usize {
+        if i == 0 {
+            0
+        } else {
+            1 << i.trailing_zeros()
+        }
+    }
+
+    fn parent(i: usize) -> usize {
+        i - Self::isolate_trailing_one(i)
+    }
+
+    fn next_sibling(i: usize) -> usize {
+        i + Self::isolate_trailing_one(i)
+    }
+
+
+
+
+}
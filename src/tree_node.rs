@@ -0,0 +1,67 @@
+//! Binary trees with boxed children: every child is an `Option<Box<TreeNode>>`.
+//! It is shared by the binaries that declare `mod tree_node;`.
+
+pub struct TreeNode {
+    pub val: i32,
+    pub left: Option<Box<TreeNode>>,
+    pub right: Option<Box<TreeNode>>,
+}
+
+/// Drops the nodes one at a time, with an explicit stack:
+/// the default drop is recursive, and overflows the stack on a deep tree (e.g. a chain of a million nodes).
+impl Drop for TreeNode {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<TreeNode>> = self.left.take().into_iter().chain(self.right.take()).collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.left.take());
+            stack.extend(node.right.take());
+            // `node` is dropped here, without children
+        }
+    }
+}
+
+/// Calls `f(node, depth)` for every node of the tree (the root has depth 1), with an explicit stack instead of recursion
+fn for_each_node(n: Option<&TreeNode>, mut f: impl FnMut(&TreeNode, i32)) {
+    let mut stack: Vec<(&TreeNode, i32)> = n.map(|root| (root, 1)).into_iter().collect();
+    while let Some((node, depth)) = stack.pop() {
+        f(node, depth);
+        stack.extend(node.left.as_deref().map(|left| (left, depth + 1)));
+        stack.extend(node.right.as_deref().map(|right| (right, depth + 1)));
+    }
+}
+
+/// # Returns
+/// the number of nodes of the tree (0 for an empty tree)
+pub fn tree_size(n: Option<&TreeNode>) -> i32 {
+    let mut size = 0;
+    for_each_node(n, |_, _| size += 1);
+    size
+}
+
+/// # Returns
+/// the number of nodes on the longest path from the root to a leaf (0 for an empty tree)
+pub fn tree_height(n: Option<&TreeNode>) -> i32 {
+    let mut height = 0;
+    for_each_node(n, |_, depth| height = height.max(depth));
+    height
+}
+
+/// # Returns
+/// the sum of the values of the tree (as `i64`, so that it does not overflow)
+pub fn tree_sum(n: Option<&TreeNode>) -> i64 {
+    let mut sum = 0;
+    for_each_node(n, |node, _| sum += node.val as i64);
+    sum
+}
+
+/// # Returns
+/// the number of nodes without children
+pub fn leaf_count(n: Option<&TreeNode>) -> i32 {
+    let mut leaves = 0;
+    for_each_node(n, |node, _| {
+        if node.left.is_none() && node.right.is_none() {
+            leaves += 1;
+        }
+    });
+    leaves
+}